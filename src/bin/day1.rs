@@ -1,72 +1,405 @@
 use aoc2024::fetch_or_load_input;
-use std::collections::HashMap;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::env;
 use std::error::Error;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::path::PathBuf;
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Number of values per column kept in memory before a sorted run is spilled to disk.
+const RUN_SIZE: usize = 1 << 20;
 
 fn main() -> Result<(), Box<dyn Error>> {
-    let day = 1;
-    let input = fetch_or_load_input(day)?;
+    let args: Vec<String> = env::args().collect();
+
+    // `day1 --matrix <path>` compares every pair of columns in a file with any number of lists
+    if let Some(idx) = args.iter().position(|arg| arg == "--matrix") {
+        let path = args.get(idx + 1).ok_or("Missing path after --matrix")?;
+        let columns = parse_columns(&fs::read_to_string(path)?)?;
+        print!("{}", ColumnReport::new(&columns));
+        return Ok(());
+    }
 
-    let total_difference = calculate_total_difference(&input)?;
-    println!("Total sum of absolute differences: {}", total_difference);
+    // `day1 --stream <path>` processes a location list that may not fit in memory
+    let metrics = match args.iter().position(|arg| arg == "--stream") {
+        Some(idx) => {
+            let path = args.get(idx + 1).ok_or("Missing path after --stream")?;
+            let reader = BufReader::new(File::open(path)?);
+            calculate_metrics_streaming(reader, RUN_SIZE)?
+        }
+        None => {
+            let day = 1;
+            let input = fetch_or_load_input(day)?;
+            calculate_metrics(&input)?
+        }
+    };
 
-    let similarity_score = calculate_similarity_score(&input)?;
-    println!("Similarity score: {}", similarity_score);
+    println!(
+        "Total sum of absolute differences: {}",
+        metrics.total_difference
+    );
+    println!("Similarity score: {}", metrics.similarity_score);
 
     Ok(())
 }
 
-fn calculate_total_difference(input: &str) -> Result<usize, Box<dyn Error>> {
-    let (mut column1, mut column2) = parse_input(input)?;
-    column1.sort();
-    column2.sort();
-    let total_difference = column1
-        .iter()
-        .zip(column2.iter())
-        .map(|(&num1, &num2)| (num1 - num2).abs() as usize)
-        .sum();
-    Ok(total_difference)
+/// Compute both metrics from a puzzle input held in memory, parsing it once.
+fn calculate_metrics(input: &str) -> Result<LocationMetrics, Box<dyn Error>> {
+    let (column1, column2) = parse_input(input)?;
+    Ok(LocationMetrics {
+        total_difference: sorted_pair_distance(&column1, &column2),
+        similarity_score: similarity_score(&column1, &column2),
+    })
+}
+
+/// Parse the two-column puzzle input.
+fn parse_input(input: &str) -> Result<(Vec<i64>, Vec<i64>), Box<dyn Error>> {
+    let mut columns = parse_columns(input)?;
+    if columns.is_empty() {
+        return Ok((Vec::new(), Vec::new()));
+    }
+    if columns.len() != 2 {
+        return Err(format!("Expected 2 columns, found {}", columns.len()).into());
+    }
+
+    let column2 = columns.pop().unwrap();
+    let column1 = columns.pop().unwrap();
+    Ok((column1, column2))
 }
 
-fn parse_input(input: &str) -> Result<(Vec<i32>, Vec<i32>), Box<dyn Error>> {
-    let mut column1 = Vec::new();
-    let mut column2 = Vec::new();
+/// Parse whitespace-separated rows into columns. Every row must have the same number of values.
+fn parse_columns(input: &str) -> Result<Vec<Vec<i64>>, Box<dyn Error>> {
+    let mut columns: Vec<Vec<i64>> = Vec::new();
 
     for line in input.lines() {
-        if line.trim().is_empty() {
+        let Some(row) = parse_row(line)? else {
             continue;
-        }
+        };
 
-        let nums: Vec<&str> = line.split_whitespace().collect();
-        if nums.len() != 2 {
+        if columns.is_empty() {
+            columns = vec![Vec::new(); row.len()];
+        } else if row.len() != columns.len() {
             return Err(format!("Invalid input line: '{}'", line).into());
         }
 
-        let num1: i32 = nums[0].parse()?;
-        let num2: i32 = nums[1].parse()?;
+        for (column, value) in columns.iter_mut().zip(row) {
+            column.push(value);
+        }
+    }
+
+    Ok(columns)
+}
 
-        column1.push(num1);
-        column2.push(num2);
+/// Parse a row of numbers. Returns None for blank lines.
+fn parse_row(line: &str) -> Result<Option<Vec<i64>>, Box<dyn Error>> {
+    if line.trim().is_empty() {
+        return Ok(None);
     }
 
-    Ok((column1, column2))
+    let row: Result<Vec<i64>, _> = line.split_whitespace().map(str::parse).collect();
+    Ok(Some(row?))
 }
 
-fn calculate_similarity_score(input: &str) -> Result<usize, Box<dyn Error>> {
-    let (column1, column2) = parse_input(input)?;
+/// Parse a line with two numbers. Returns None for blank lines.
+fn parse_line(line: &str) -> Result<Option<(i64, i64)>, Box<dyn Error>> {
+    match parse_row(line)?.as_deref() {
+        None => Ok(None),
+        Some(&[num1, num2]) => Ok(Some((num1, num2))),
+        Some(_) => Err(format!("Invalid input line: '{}'", line).into()),
+    }
+}
+
+/// Sum of absolute differences after pairing the smallest values, the second smallest, and so on.
+fn sorted_pair_distance(column1: &[i64], column2: &[i64]) -> u64 {
+    let mut column1 = column1.to_vec();
+    let mut column2 = column2.to_vec();
+    column1.sort();
+    column2.sort();
+    column1
+        .iter()
+        .zip(column2.iter())
+        .map(|(&num1, &num2)| (num1 - num2).unsigned_abs())
+        .sum()
+}
 
+/// Each value in `column1` weighted by how often it appears in `column2`.
+fn similarity_score(column1: &[i64], column2: &[i64]) -> i64 {
     // Build frequency map for the second column
     let mut freq_map = HashMap::new();
-    for &num in &column2 {
+    for &num in column2 {
         *freq_map.entry(num).or_insert(0) += 1;
     }
 
     // Calculate the similarity score
-    let similarity_score = column1
+    column1
         .iter()
-        .map(|&num| num as usize * freq_map.get(&num).cloned().unwrap_or(0))
-        .sum();
+        .map(|&num| num * freq_map.get(&num).cloned().unwrap_or(0))
+        .sum()
+}
+
+/// Distance and similarity for every ordered pair of columns.
+/// `distances[i][j]` is symmetric; `similarities[i][j]` weights column i by frequencies in column j.
+struct ColumnReport {
+    distances: Vec<Vec<u64>>,
+    similarities: Vec<Vec<i64>>,
+}
+
+impl ColumnReport {
+    fn new(columns: &[Vec<i64>]) -> Self {
+        let n = columns.len();
+        let mut distances = vec![vec![0; n]; n];
+        let mut similarities = vec![vec![0; n]; n];
+
+        for i in 0..n {
+            for j in 0..n {
+                if j > i {
+                    distances[i][j] = sorted_pair_distance(&columns[i], &columns[j]);
+                    distances[j][i] = distances[i][j];
+                }
+                similarities[i][j] = similarity_score(&columns[i], &columns[j]);
+            }
+        }
+
+        ColumnReport {
+            distances,
+            similarities,
+        }
+    }
+}
+
+impl std::fmt::Display for ColumnReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(f, "Sorted-pair distances:")?;
+        for row in &self.distances {
+            let cells: Vec<String> = row.iter().map(|d| format!("{:>12}", d)).collect();
+            writeln!(f, "{}", cells.join(" "))?;
+        }
+        writeln!(f, "Similarity scores (row weighted by column frequencies):")?;
+        for row in &self.similarities {
+            let cells: Vec<String> = row.iter().map(|s| format!("{:>12}", s)).collect();
+            writeln!(f, "{}", cells.join(" "))?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct LocationMetrics {
+    total_difference: u64,
+    similarity_score: i64,
+}
+
+/// Compute both metrics in a single pass over the input without holding the columns in memory.
+/// Each column is sorted externally: values are buffered up to `run_size`, spilled to temp
+/// files as sorted runs, and the runs are k-way merged back into one sorted stream.
+fn calculate_metrics_streaming<R: BufRead>(
+    reader: R,
+    run_size: usize,
+) -> Result<LocationMetrics, Box<dyn Error>> {
+    let mut sorter1 = ExternalSorter::new(run_size);
+    let mut sorter2 = ExternalSorter::new(run_size);
+
+    for line in reader.lines() {
+        if let Some((num1, num2)) = parse_line(&line?)? {
+            sorter1.push(num1)?;
+            sorter2.push(num2)?;
+        }
+    }
+
+    metrics_from_sorted(sorter1.finish()?, sorter2.finish()?)
+}
+
+/// Walk two sorted streams of equal length in value order.
+///
+/// The similarity score is the sum of v * count1(v) * count2(v). The sum of |a_i - b_i| over
+/// the sorted pairs equals the area between the two cumulative counts, so it can be
+/// accumulated between consecutive distinct values without pairing elements by index.
+fn metrics_from_sorted(
+    mut column1: MergedRuns,
+    mut column2: MergedRuns,
+) -> Result<LocationMetrics, Box<dyn Error>> {
+    let mut total_difference = 0u64;
+    let mut similarity_score = 0i64;
+    let mut cumulative1 = 0i64;
+    let mut cumulative2 = 0i64;
+
+    let mut next1 = column1.next_value()?;
+    let mut next2 = column2.next_value()?;
+
+    while let Some(value) = match (next1, next2) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    } {
+        let mut count1 = 0;
+        while next1 == Some(value) {
+            count1 += 1;
+            next1 = column1.next_value()?;
+        }
+        let mut count2 = 0;
+        while next2 == Some(value) {
+            count2 += 1;
+            next2 = column2.next_value()?;
+        }
+
+        similarity_score += value * count1 * count2;
+        cumulative1 += count1;
+        cumulative2 += count2;
+
+        let following = match (next1, next2) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+        if let Some(following) = following {
+            total_difference +=
+                (cumulative1 - cumulative2).unsigned_abs() * (following - value) as u64;
+        }
+    }
+
+    if cumulative1 != cumulative2 {
+        return Err("Columns have different lengths".into());
+    }
+
+    Ok(LocationMetrics {
+        total_difference,
+        similarity_score,
+    })
+}
+
+static RUN_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// A sorted run of values spilled to a temp file, removed again when dropped.
+struct SpilledRun {
+    path: PathBuf,
+}
+
+impl SpilledRun {
+    fn path(id: usize) -> PathBuf {
+        env::temp_dir().join(format!("aoc2024_day1_{}_{}.run", process::id(), id))
+    }
+
+    fn write(values: &[i64]) -> io::Result<Self> {
+        // Never truncate a file or follow a symlink that is already there, and only
+        // take ownership of the path (and its removal) once we have created it
+        let (file, run) = loop {
+            let path = Self::path(RUN_COUNTER.fetch_add(1, Ordering::Relaxed));
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(file) => break (file, SpilledRun { path }),
+                Err(err) if err.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(err) => return Err(err),
+            }
+        };
+        let mut writer = BufWriter::new(file);
+        for value in values {
+            writer.write_all(&value.to_le_bytes())?;
+        }
+        writer.flush()?;
+
+        Ok(run)
+    }
+}
+
+impl Drop for SpilledRun {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Collects values, spilling a sorted run to disk every `run_size` values.
+struct ExternalSorter {
+    run_size: usize,
+    buffer: Vec<i64>,
+    runs: Vec<SpilledRun>,
+}
+
+impl ExternalSorter {
+    fn new(run_size: usize) -> Self {
+        ExternalSorter {
+            run_size: run_size.max(1),
+            buffer: Vec::new(),
+            runs: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, value: i64) -> io::Result<()> {
+        self.buffer.push(value);
+        if self.buffer.len() >= self.run_size {
+            self.buffer.sort_unstable();
+            self.runs.push(SpilledRun::write(&self.buffer)?);
+            self.buffer.clear();
+        }
+        Ok(())
+    }
+
+    /// Sort the remaining buffer in memory and merge it with the spilled runs.
+    fn finish(mut self) -> io::Result<MergedRuns> {
+        self.buffer.sort_unstable();
+
+        let mut sources = Vec::with_capacity(self.runs.len() + 1);
+        for run in &self.runs {
+            sources.push(RunSource::File(BufReader::new(File::open(&run.path)?)));
+        }
+        sources.push(RunSource::Memory(self.buffer.into_iter()));
+
+        MergedRuns::new(sources, self.runs)
+    }
+}
+
+enum RunSource {
+    File(BufReader<File>),
+    Memory(std::vec::IntoIter<i64>),
+}
 
-    Ok(similarity_score)
+impl RunSource {
+    fn next_value(&mut self) -> io::Result<Option<i64>> {
+        match self {
+            RunSource::File(reader) => {
+                let mut bytes = [0u8; 8];
+                match reader.read_exact(&mut bytes) {
+                    Ok(()) => Ok(Some(i64::from_le_bytes(bytes))),
+                    Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(None),
+                    Err(e) => Err(e),
+                }
+            }
+            RunSource::Memory(values) => Ok(values.next()),
+        }
+    }
+}
+
+/// K-way merge of sorted runs using a min-heap keyed on each run's current head.
+struct MergedRuns {
+    sources: Vec<RunSource>,
+    heap: BinaryHeap<Reverse<(i64, usize)>>,
+    // Keeps the temp files alive until the merge is done
+    _runs: Vec<SpilledRun>,
+}
+
+impl MergedRuns {
+    fn new(mut sources: Vec<RunSource>, runs: Vec<SpilledRun>) -> io::Result<Self> {
+        let mut heap = BinaryHeap::new();
+        for (idx, source) in sources.iter_mut().enumerate() {
+            if let Some(value) = source.next_value()? {
+                heap.push(Reverse((value, idx)));
+            }
+        }
+        Ok(MergedRuns {
+            sources,
+            heap,
+            _runs: runs,
+        })
+    }
+
+    fn next_value(&mut self) -> io::Result<Option<i64>> {
+        let Some(Reverse((value, idx))) = self.heap.pop() else {
+            return Ok(None);
+        };
+        if let Some(next) = self.sources[idx].next_value()? {
+            self.heap.push(Reverse((next, idx)));
+        }
+        Ok(Some(value))
+    }
 }
 
 #[cfg(test)]
@@ -83,13 +416,81 @@ mod tests {
 
     #[test]
     fn test_calculate_total_difference() {
-        let result = calculate_total_difference(TEST_DATA).unwrap();
-        assert_eq!(result, 11);
+        let metrics = calculate_metrics(TEST_DATA).unwrap();
+        assert_eq!(metrics.total_difference, 11);
     }
 
     #[test]
     fn test_calculate_similarity_score() {
-        let result = calculate_similarity_score(TEST_DATA).unwrap();
-        assert_eq!(result, 31);
+        let metrics = calculate_metrics(TEST_DATA).unwrap();
+        assert_eq!(metrics.similarity_score, 31);
+    }
+
+    #[test]
+    fn test_column_report() {
+        let input = "\
+3   4   3
+4   3   3
+2   5   1
+1   3   2
+3   9   5
+3   3   3";
+        let columns = parse_columns(input).unwrap();
+        let report = ColumnReport::new(&columns);
+
+        // The first two columns are the puzzle example
+        assert_eq!(report.distances[0][1], 11);
+        assert_eq!(report.similarities[0][1], 31);
+
+        assert_eq!(
+            report.distances,
+            vec![vec![0, 11, 1], vec![11, 0, 10], vec![1, 10, 0]]
+        );
+        // 3 * 3 + 2 + 1 + 3 * 3 + 3 * 3, the 4 has no match in the third column
+        assert_eq!(report.similarities[0][2], 30);
+        // 3 * 3 + 3 * 3 + 1 + 2 + 3 * 3, the 5 has no match in the first column
+        assert_eq!(report.similarities[2][0], 30);
+    }
+
+    #[test]
+    fn test_parse_input_rejects_extra_columns() {
+        assert!(parse_input("1 2 3\n4 5 6").is_err());
+        assert!(parse_columns("1 2 3\n4 5").is_err());
+    }
+
+    #[test]
+    fn test_streaming_matches_in_memory() {
+        // A run size of 2 forces several spilled runs per column
+        let metrics = calculate_metrics_streaming(TEST_DATA.as_bytes(), 2).unwrap();
+
+        let expected_metrics = LocationMetrics {
+            total_difference: 11,
+            similarity_score: 31,
+        };
+        assert_eq!(metrics, expected_metrics);
+        assert_eq!(calculate_metrics(TEST_DATA).unwrap(), expected_metrics);
+    }
+
+    #[test]
+    fn test_streaming_does_not_overflow() {
+        // The difference alone does not fit in an i32
+        let input = "-2000000000   2000000000\n5   5\n";
+        let metrics = calculate_metrics_streaming(input.as_bytes(), 1).unwrap();
+
+        assert_eq!(metrics.total_difference, 4_000_000_000);
+        assert_eq!(metrics.similarity_score, 5);
+    }
+
+    #[test]
+    fn test_spilled_run_keeps_existing_file() {
+        let existing = SpilledRun::path(RUN_COUNTER.load(Ordering::Relaxed));
+        fs::write(&existing, b"keep me").unwrap();
+
+        let run = SpilledRun::write(&[1, 2, 3]).unwrap();
+        assert_ne!(run.path, existing);
+        drop(run);
+
+        assert_eq!(fs::read(&existing).unwrap(), b"keep me");
+        fs::remove_file(&existing).unwrap();
     }
 }
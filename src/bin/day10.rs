@@ -1,17 +1,46 @@
 use aoc2024::fetch_or_load_input;
-use std::collections::HashSet;
+use std::collections::VecDeque;
+use std::env;
 use std::error::Error;
+use std::fs;
 
 fn main() -> Result<(), Box<dyn Error>> {
     let day = 10;
     let input = fetch_or_load_input(day)?;
     let grid = parse_input(&input);
 
+    // `day10 --rule ascending|updown|rise:K` changes which steps a trail may take
+    let args: Vec<String> = env::args().collect();
+    let rule = match args.iter().position(|arg| arg == "--rule") {
+        Some(idx) => StepRule::parse(args.get(idx + 1).ok_or("Missing rule after --rule")?)?,
+        None => StepRule::Ascending,
+    };
+
+    // `day10 --trails 100 trails.txt` writes out the first trails
+    if let Some(idx) = args.iter().position(|arg| arg == "--trails") {
+        let limit: usize = args
+            .get(idx + 1)
+            .ok_or("Missing limit after --trails")?
+            .parse()?;
+        let path = args
+            .get(idx + 2)
+            .ok_or("Missing path after --trails limit")?;
+        let trails = enumerate_trails(&grid, rule, limit);
+        fs::write(path, format_trails(&trails))?;
+        println!("Wrote {} trails to {}", trails.len(), path);
+    }
+
     // Compute both types of scores
-    let (score_part_one, score_part_two) = find_all_trails_score(&grid);
+    let (score_part_one, score_part_two) = find_all_trails_score(&grid, rule, MAX_WALK_STEPS);
 
     println!("Score (part one): {}", score_part_one);
-    println!("Score (part two): {}", score_part_two);
+    match score_part_two {
+        Some(score) => println!("Score (part two): {}", score),
+        None => println!(
+            "Score (part two): too many trails, gave up after {} steps",
+            MAX_WALK_STEPS
+        ),
+    }
 
     Ok(())
 }
@@ -24,108 +53,305 @@ fn parse_input(input: &str) -> Vec<Vec<u8>> {
         .collect()
 }
 
+/// Which neighboring heights a trail may step onto.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum StepRule {
+    /// Exactly one higher
+    Ascending,
+    /// One higher or one lower
+    UpOrDown,
+    /// Higher by at least one and at most `k`
+    RiseUpTo(u8),
+}
+
+impl StepRule {
+    /// Parse `ascending`, `updown` or `rise:K`.
+    fn parse(text: &str) -> Result<StepRule, Box<dyn Error>> {
+        match text.split_once(':') {
+            None if text == "ascending" => Ok(StepRule::Ascending),
+            None if text == "updown" => Ok(StepRule::UpOrDown),
+            Some(("rise", k)) => Ok(StepRule::RiseUpTo(k.parse()?)),
+            _ => Err(format!("Unknown step rule: '{}'", text).into()),
+        }
+    }
+
+    fn allows(self, from: u8, to: u8) -> bool {
+        match self {
+            StepRule::Ascending => to == from + 1,
+            StepRule::UpOrDown => to.abs_diff(from) == 1,
+            StepRule::RiseUpTo(k) => to > from && to - from <= k,
+        }
+    }
+
+    /// Trails under a rising rule can never come back to a cell.
+    fn is_rising(self) -> bool {
+        !matches!(self, StepRule::UpOrDown)
+    }
+}
+
+/// A fixed-size set of indices stored as bits.
 #[derive(Clone)]
-struct StateResult {
-    total_paths: i64,
-    endpoints: HashSet<(usize, usize)>,
+struct BitSet {
+    words: Vec<u64>,
 }
 
-impl StateResult {
-    fn new() -> Self {
-        Self {
-            total_paths: 0,
-            endpoints: HashSet::new(),
+impl BitSet {
+    fn new(len: usize) -> Self {
+        BitSet {
+            words: vec![0; len.div_ceil(64)],
         }
     }
 
-    fn single_endpoint(r: usize, c: usize) -> Self {
-        let mut s = Self::new();
-        s.total_paths = 1;
-        s.endpoints.insert((r, c));
-        s
+    /// Add an index, returning true if it was not already present.
+    fn insert(&mut self, idx: usize) -> bool {
+        let (word, bit) = (idx / 64, 1u64 << (idx % 64));
+        let added = self.words[word] & bit == 0;
+        self.words[word] |= bit;
+        added
     }
 
-    fn merge(&mut self, other: &StateResult) {
-        self.total_paths += other.total_paths;
-        for &e in &other.endpoints {
-            self.endpoints.insert(e);
+    fn remove(&mut self, idx: usize) {
+        self.words[idx / 64] &= !(1u64 << (idx % 64));
+    }
+
+    fn union_with(&mut self, other: &BitSet) {
+        for (word, other_word) in self.words.iter_mut().zip(&other.words) {
+            *word |= other_word;
         }
     }
+
+    fn count(&self) -> usize {
+        self.words
+            .iter()
+            .map(|word| word.count_ones() as usize)
+            .sum()
+    }
+}
+
+type Position = (usize, usize);
+
+/// The in-bounds neighbors of a cell: up, down, left, right.
+fn neighbors(grid: &[Vec<u8>], (r, c): Position) -> impl Iterator<Item = Position> + '_ {
+    let directions = [(-1, 0), (1, 0), (0, -1), (0, 1)];
+    directions.into_iter().filter_map(move |(dr, dc)| {
+        let nr = r.checked_add_signed(dr)?;
+        let nc = c.checked_add_signed(dc)?;
+        (nr < grid.len() && nc < grid[nr].len()).then_some((nr, nc))
+    })
+}
+
+/// The cells a trail may step to from `pos` under the rule. A trail ends at height 9.
+fn next_steps(
+    grid: &[Vec<u8>],
+    pos: Position,
+    rule: StepRule,
+) -> impl Iterator<Item = Position> + '_ {
+    let height = grid[pos.0][pos.1];
+    neighbors(grid, pos).filter(move |&(nr, nc)| height != 9 && rule.allows(height, grid[nr][nc]))
+}
+
+fn trailheads(grid: &[Vec<u8>]) -> impl Iterator<Item = Position> + '_ {
+    grid.iter().enumerate().flat_map(|(r, row)| {
+        row.iter()
+            .enumerate()
+            .filter(|&(_, &height)| height == 0)
+            .map(move |(c, _)| (r, c))
+    })
 }
 
+/// How many cells the walk over winding trails may step onto before giving up.
+const MAX_WALK_STEPS: u64 = 10_000_000;
+
 /// Find all trails.
 /// Returns a tuple: (score_part_one, score_part_two).
-/// score_part_one = sum of endpoints count from each '0' cell
-/// score_part_two = sum of total_paths from each '0' cell
-fn find_all_trails_score(grid: &[Vec<u8>]) -> (usize, i64) {
-    let rows = grid.len();
-    let cols = if rows == 0 { 0 } else { grid[0].len() };
-
-    // memo[row][col][digit]: Option<StateResult>
-    let mut memo = vec![vec![vec![None; 10]; cols]; rows];
-
-    let mut score_part_one = 0usize;
-    let mut score_part_two = 0i64;
-
-    for r in 0..rows {
-        for c in 0..cols {
-            if grid[r][c] == 0 {
-                // Get StateResult
-                let res = dfs(grid, r, c, 0, &mut memo);
-                score_part_one += res.endpoints.len();
-                score_part_two += res.total_paths;
+/// score_part_one = sum over trailheads of the number of reachable 9s
+/// score_part_two = sum over trailheads of the number of distinct trails, or None
+/// if counting them takes more than `max_steps` steps
+fn find_all_trails_score(grid: &[Vec<u8>], rule: StepRule, max_steps: u64) -> (usize, Option<u64>) {
+    if rule.is_rising() {
+        let (score, rating) = rising_trails_score(grid, rule);
+        (score, Some(rating))
+    } else {
+        winding_trails_score(grid, rule, max_steps)
+    }
+}
+
+/// Scores for rules where every step goes up. Heights are processed from 9 down to 0,
+/// so each cell combines the already known results of the cells it can step to:
+/// a bitset of reachable 9s and a count of trails. A height's bitsets are dropped as
+/// soon as no lower height can step onto it, so only a few layers are kept at a time.
+fn rising_trails_score(grid: &[Vec<u8>], rule: StepRule) -> (usize, u64) {
+    let cols = grid.first().map_or(0, |row| row.len());
+    let index = |(r, c): Position| r * cols + c;
+
+    let mut layers: Vec<Vec<Position>> = vec![Vec::new(); 10];
+    for (r, row) in grid.iter().enumerate() {
+        for (c, &height) in row.iter().enumerate() {
+            layers[height as usize].push((r, c));
+        }
+    }
+    let max_rise = match rule {
+        StepRule::RiseUpTo(k) => k as usize,
+        _ => 1,
+    };
+
+    let mut reachable: Vec<Option<BitSet>> = vec![None; grid.len() * cols];
+    let mut trails = vec![0u64; grid.len() * cols];
+    let (mut score, mut rating) = (0, 0);
+    for height in (0..layers.len()).rev() {
+        for (i, &pos) in layers[height].iter().enumerate() {
+            let mut reach = BitSet::new(layers[9].len());
+            if height == 9 {
+                // The 9s are numbered by their place in the top layer
+                reach.insert(i);
+                trails[index(pos)] = 1;
+            } else {
+                for next in next_steps(grid, pos, rule) {
+                    if let Some(next_reach) = &reachable[index(next)] {
+                        reach.union_with(next_reach);
+                    }
+                    trails[index(pos)] += trails[index(next)];
+                }
+            }
+
+            if height == 0 {
+                score += reach.count();
+                rating += trails[index(pos)];
+            } else {
+                reachable[index(pos)] = Some(reach);
+            }
+        }
+
+        // Lower heights can step at most to height - 1 + max_rise
+        if let Some(done) = layers.get(height + max_rise) {
+            for &pos in done {
+                reachable[index(pos)] = None;
             }
         }
     }
 
-    (score_part_one, score_part_two)
+    (score, rating)
 }
 
-fn dfs(
-    grid: &[Vec<u8>],
-    r: usize,
-    c: usize,
-    digit: u8,
-    memo: &mut Vec<Vec<Vec<Option<StateResult>>>>,
-) -> StateResult {
-    if let Some(ref res) = memo[r][c][digit as usize] {
-        return res.clone();
-    }
+/// Scores for rules where trails can wind up and down. Reachable 9s are found with a
+/// breadth-first search, but trails may not visit a cell twice, so counting them means
+/// walking every simple path. That can take exponential time, so the count gives up
+/// with None after `max_steps` steps.
+fn winding_trails_score(grid: &[Vec<u8>], rule: StepRule, max_steps: u64) -> (usize, Option<u64>) {
+    let cols = grid.first().map_or(0, |row| row.len());
+    let index = |(r, c): Position| r * cols + c;
 
-    let mut res = StateResult::new();
+    let mut score = 0;
+    let mut rating = Some(0);
+    let mut budget = max_steps;
+    for head in trailheads(grid) {
+        // Breadth-first search for the reachable 9s
+        let mut seen = BitSet::new(grid.len() * cols);
+        seen.insert(index(head));
+        let mut queue = VecDeque::from([head]);
+        while let Some(pos) = queue.pop_front() {
+            if grid[pos.0][pos.1] == 9 {
+                score += 1;
+            }
+            for next in next_steps(grid, pos, rule) {
+                if seen.insert(index(next)) {
+                    queue.push_back(next);
+                }
+            }
+        }
 
-    if grid[r][c] != digit {
-        // mismatch: no paths
-        memo[r][c][digit as usize] = Some(res.clone());
-        return res;
+        if let Some(total) = rating {
+            let mut count = 0;
+            let finished = walk_trails(grid, rule, head, &mut budget, &mut |_| {
+                count += 1;
+                true
+            });
+            rating = finished.then_some(total + count);
+        }
     }
 
-    if digit == 9 {
-        // endpoint
-        res = StateResult::single_endpoint(r, c);
-        memo[r][c][9] = Some(res.clone());
-        return res;
+    (score, rating)
+}
+
+/// Call `visit` with every trail from `head`, as the list of cells along it,
+/// until `visit` returns false. Every step onto a cell uses up one unit of `budget`.
+/// Returns false if the walk was stopped or ran out of budget.
+fn walk_trails(
+    grid: &[Vec<u8>],
+    rule: StepRule,
+    head: Position,
+    budget: &mut u64,
+    visit: &mut dyn FnMut(&[Position]) -> bool,
+) -> bool {
+    fn walk(
+        grid: &[Vec<u8>],
+        rule: StepRule,
+        path: &mut Vec<Position>,
+        on_path: &mut BitSet,
+        budget: &mut u64,
+        visit: &mut dyn FnMut(&[Position]) -> bool,
+    ) -> bool {
+        let pos = path[path.len() - 1];
+        if grid[pos.0][pos.1] == 9 {
+            return visit(path);
+        }
+
+        let cols = grid[0].len();
+        let steps: Vec<Position> = next_steps(grid, pos, rule).collect();
+        for next in steps {
+            if *budget == 0 {
+                return false;
+            }
+            if !on_path.insert(next.0 * cols + next.1) {
+                continue;
+            }
+            *budget -= 1;
+            path.push(next);
+            let keep_going = walk(grid, rule, path, on_path, budget, visit);
+            path.pop();
+            on_path.remove(next.0 * cols + next.1);
+            if !keep_going {
+                return false;
+            }
+        }
+        true
     }
 
-    let directions = [(-1, 0), (1, 0), (0, -1), (0, 1)];
-    let rows = grid.len();
     let cols = grid[0].len();
-    let next_digit = digit + 1;
-
-    for &(dr, dc) in &directions {
-        let nr = r as isize + dr;
-        let nc = c as isize + dc;
-        if nr >= 0 && nr < rows as isize && nc >= 0 && nc < cols as isize {
-            let (nr, nc) = (nr as usize, nc as usize);
-            if grid[nr][nc] == next_digit {
-                let subres = dfs(grid, nr, nc, next_digit, memo);
-                res.merge(&subres);
-            }
+    let mut on_path = BitSet::new(grid.len() * cols);
+    on_path.insert(head.0 * cols + head.1);
+    walk(grid, rule, &mut vec![head], &mut on_path, budget, visit)
+}
+
+/// List up to `limit` trails, from trailheads in reading order.
+fn enumerate_trails(grid: &[Vec<u8>], rule: StepRule, limit: usize) -> Vec<Vec<Position>> {
+    let mut trails = Vec::new();
+    for head in trailheads(grid) {
+        if trails.len() >= limit {
+            break;
         }
+        let mut unlimited = u64::MAX;
+        walk_trails(grid, rule, head, &mut unlimited, &mut |trail| {
+            trails.push(trail.to_vec());
+            trails.len() < limit
+        });
     }
+    trails
+}
 
-    memo[r][c][digit as usize] = Some(res.clone());
-    res
+/// One trail per line as `row,col` cells joined by arrows.
+fn format_trails(trails: &[Vec<Position>]) -> String {
+    trails
+        .iter()
+        .map(|trail| {
+            trail
+                .iter()
+                .map(|(r, c)| format!("{},{}", r, c))
+                .collect::<Vec<_>>()
+                .join(" -> ")
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
 }
 
 #[cfg(test)]
@@ -145,12 +371,78 @@ mod tests {
     #[test]
     fn test_part_one_and_part_two() {
         let grid = parse_input(TEST_DATA);
-        let (score_part_one, score_part_two) = find_all_trails_score(&grid);
+        let (score_part_one, score_part_two) =
+            find_all_trails_score(&grid, StepRule::Ascending, MAX_WALK_STEPS);
 
         let expected_score_part_one = 36;
         assert_eq!(score_part_one, expected_score_part_one);
 
-        let expected_score_part_two = 81;
+        let expected_score_part_two = Some(81);
         assert_eq!(score_part_two, expected_score_part_two);
     }
+
+    #[test]
+    fn test_enumerate_trails() {
+        let grid = parse_input(TEST_DATA);
+        let trails = enumerate_trails(&grid, StepRule::Ascending, usize::MAX);
+        assert_eq!(trails.len(), 81);
+
+        let first_three = enumerate_trails(&grid, StepRule::Ascending, 3);
+        assert_eq!(first_three, trails[..3].to_vec());
+        assert_eq!(
+            format_trails(&first_three[..1]),
+            "0,2 -> 1,2 -> 1,3 -> 2,3 -> 3,3 -> 3,2 -> 4,2 -> 4,3 -> 4,4 -> 3,4"
+        );
+    }
+
+    #[test]
+    fn test_step_rules() {
+        let grid = parse_input("0123\n9874\n0965");
+
+        // The single climb 0 -> 8 spirals around and then splits to the two 9s
+        assert_eq!(
+            find_all_trails_score(&grid, StepRule::Ascending, MAX_WALK_STEPS),
+            (2, Some(2))
+        );
+
+        // Bigger steps add shortcuts, but the lower 0 is still walled in by 9s
+        let (score, rating) = find_all_trails_score(&grid, StepRule::RiseUpTo(2), MAX_WALK_STEPS);
+        assert_eq!(
+            rating.unwrap() as usize,
+            enumerate_trails(&grid, StepRule::RiseUpTo(2), 100).len()
+        );
+        assert_eq!(score, 2);
+
+        // Going down again is allowed, but cells are never revisited on a trail
+        let (score, rating) = find_all_trails_score(&grid, StepRule::UpOrDown, MAX_WALK_STEPS);
+        assert_eq!((score, rating), (2, Some(2)));
+
+        // From the 6, either climb straight to the 7 below it, or go round
+        // 7 -> 8 and step down onto that same 7, before 8 -> 9
+        let grid = parse_input("0123\n7654\n8789");
+        assert_eq!(
+            find_all_trails_score(&grid, StepRule::Ascending, MAX_WALK_STEPS),
+            (1, Some(1))
+        );
+        assert_eq!(
+            find_all_trails_score(&grid, StepRule::UpOrDown, MAX_WALK_STEPS),
+            (1, Some(2))
+        );
+    }
+
+    #[test]
+    fn test_winding_trails_give_up() {
+        let grid = parse_input(TEST_DATA);
+
+        // The reachable 9s are still found when counting the trails takes too long
+        let (score, rating) = find_all_trails_score(&grid, StepRule::UpOrDown, 10);
+        assert_eq!(rating, None);
+        let (full_score, full_rating) =
+            find_all_trails_score(&grid, StepRule::UpOrDown, MAX_WALK_STEPS);
+        assert_eq!(score, full_score);
+        assert!(full_rating.is_some());
+
+        // Listing a few trails does not need the full count
+        assert_eq!(enumerate_trails(&grid, StepRule::UpOrDown, 5).len(), 5);
+    }
 }
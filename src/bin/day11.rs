@@ -1,5 +1,7 @@
 use aoc2024::fetch_or_load_input;
-use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+use std::env;
 use std::error::Error;
 
 fn main() -> Result<(), Box<dyn Error>> {
@@ -7,18 +9,75 @@ fn main() -> Result<(), Box<dyn Error>> {
     let input = fetch_or_load_input(day)?;
     let map = parse_input(&input)?;
 
-    let count_part_one = evolve_sequence(&map, 25);
+    let rules = RuleSet::standard();
+
+    let count_part_one = evolve_sequence(&map, 25, &rules, None)?;
     println!("Total count part one: {}", count_part_one);
 
-    let count_part_two = evolve_sequence(&map, 75);
+    let count_part_two = evolve_sequence(&map, 75, &rules, None)?;
     println!("Total count part two: {}", count_part_two);
 
+    // `day11 --rules "0->1, split, *2024" [--blinks N] [--modulus M]` blinks with another
+    // rule set, and `--accelerate N` instead of `--blinks N` handles a huge number of blinks
+    let args: Vec<String> = env::args().collect();
+    let rules = match args.iter().position(|arg| arg == "--rules") {
+        Some(idx) => RuleSet::parse(args.get(idx + 1).ok_or("Missing rules after --rules")?)?,
+        None => rules,
+    };
+    let modulus = match args.iter().position(|arg| arg == "--modulus") {
+        Some(idx) => Some(args.get(idx + 1).ok_or("Missing modulus")?.parse()?),
+        None => None,
+    };
+    let modulus_suffix = modulus.map_or(String::new(), |modulus| format!(" mod {}", modulus));
+
+    if let Some(idx) = args.iter().position(|arg| arg == "--blinks") {
+        let blinks = args.get(idx + 1).ok_or("Missing count after --blinks")?;
+        let count = evolve_sequence(&map, blinks.parse()?, &rules, modulus)?;
+        println!(
+            "Total count after {} blinks{}: {}",
+            blinks, modulus_suffix, count
+        );
+    }
+
+    if let Some(idx) = args.iter().position(|arg| arg == "--accelerate") {
+        let blinks = args
+            .get(idx + 1)
+            .ok_or("Missing count after --accelerate")?;
+        let count = accelerated_count(&map, blinks.parse()?, &rules, modulus)?;
+        println!(
+            "Total count after {} blinks{}: {}",
+            blinks, modulus_suffix, count
+        );
+    }
+
+    // `day11 --distinct N` reports how many different numbers there are at each blink
+    if let Some(idx) = args.iter().position(|arg| arg == "--distinct") {
+        let blinks = args.get(idx + 1).ok_or("Missing count after --distinct")?;
+        let distinct = distinct_values_per_blink(&map, blinks.parse()?, &rules)?;
+        for (blink, count) in distinct.iter().enumerate() {
+            println!("Blink {}: {} distinct values", blink, count);
+        }
+        let closure = find_closure(&map, &rules, MAX_CLOSURE_SIZE)?;
+        println!(
+            "All {} values have appeared after {} blinks",
+            closure.values.len(),
+            closure.blinks
+        );
+    }
+
     Ok(())
 }
 
-fn parse_input(line: &str) -> Result<HashMap<usize, usize>, Box<dyn Error>> {
+/// The number engraved on a stone.
+type Stone = u64;
+
+/// How many stones carry the same number. Counts grow exponentially with the
+/// number of blinks, so they get more room than the stones themselves.
+type Count = u128;
+
+fn parse_input(line: &str) -> Result<HashMap<Stone, Count>, Box<dyn Error>> {
     let mut map = HashMap::new();
-    let numbers: Result<Vec<usize>, _> = line.split_whitespace().map(|s| s.parse()).collect();
+    let numbers: Result<Vec<Stone>, _> = line.split_whitespace().map(|s| s.parse()).collect();
     let numbers = numbers?;
     for num in numbers {
         *map.entry(num).or_insert(0) += 1;
@@ -26,42 +85,377 @@ fn parse_input(line: &str) -> Result<HashMap<usize, usize>, Box<dyn Error>> {
     Ok(map)
 }
 
+/// One way a stone can change when you blink.
+#[derive(Clone, Debug, PartialEq)]
+enum Rule {
+    /// A stone engraved with the first number is replaced by one with the second
+    Replace(Stone, Stone),
+    /// A stone with an even number of digits splits into its left and right halves
+    SplitEvenDigits,
+    /// The number on the stone is multiplied by the factor
+    Multiply(Stone),
+}
+
+impl Rule {
+    /// Parse `A->B`, `split` or `*K`.
+    fn parse(text: &str) -> Result<Rule, Box<dyn Error>> {
+        let text = text.trim();
+        if text == "split" {
+            Ok(Rule::SplitEvenDigits)
+        } else if let Some(factor) = text.strip_prefix('*') {
+            Ok(Rule::Multiply(factor.trim().parse()?))
+        } else if let Some((from, to)) = text.split_once("->") {
+            Ok(Rule::Replace(from.trim().parse()?, to.trim().parse()?))
+        } else {
+            Err(format!("Unknown stone rule: '{}'", text).into())
+        }
+    }
+
+    /// The stones that replace `stone`, or None if the rule does not apply to it.
+    /// Errors if a new number does not fit on a stone.
+    fn apply(&self, stone: Stone) -> Result<Option<Vec<Stone>>, Box<dyn Error>> {
+        match *self {
+            Rule::Replace(from, to) => Ok((stone == from).then(|| vec![to])),
+            Rule::SplitEvenDigits => {
+                let digits = stone.checked_ilog10().unwrap_or(0) + 1;
+                if !digits.is_multiple_of(2) {
+                    return Ok(None);
+                }
+                let denominator = 10_u64.pow(digits / 2);
+                Ok(Some(vec![stone / denominator, stone % denominator]))
+            }
+            Rule::Multiply(factor) => match stone.checked_mul(factor) {
+                Some(product) => Ok(Some(vec![product])),
+                None => Err(format!("Stone {} times {} overflows", stone, factor).into()),
+            },
+        }
+    }
+}
+
+/// Rules in order of priority. The first rule that applies to a stone decides
+/// what it turns into.
+#[derive(Clone, Debug, PartialEq)]
+struct RuleSet {
+    rules: Vec<Rule>,
+}
+
+impl RuleSet {
+    /// Zero becomes one, even-digit numbers split, and everything else is multiplied by 2024.
+    fn standard() -> RuleSet {
+        RuleSet {
+            rules: vec![
+                Rule::Replace(0, 1),
+                Rule::SplitEvenDigits,
+                Rule::Multiply(2024),
+            ],
+        }
+    }
+
+    /// Parse a comma separated list of rules, e.g. `0->1, split, *2024`.
+    fn parse(text: &str) -> Result<RuleSet, Box<dyn Error>> {
+        let rules = text
+            .split(',')
+            .map(Rule::parse)
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(RuleSet { rules })
+    }
+
+    /// The stones that replace `stone` after one blink.
+    fn apply(&self, stone: Stone) -> Result<Vec<Stone>, Box<dyn Error>> {
+        for rule in &self.rules {
+            if let Some(stones) = rule.apply(stone)? {
+                return Ok(stones);
+            }
+        }
+        Err(format!("No rule applies to stone {}", stone).into())
+    }
+}
+
+/// Blink once, applying the rules to every distinct number in the map.
+fn blink(
+    current: &HashMap<Stone, Count>,
+    rules: &RuleSet,
+    counting: Counting,
+) -> Result<HashMap<Stone, Count>, Box<dyn Error>> {
+    let mut next = HashMap::new();
+    for (&stone, &count) in current {
+        for new_stone in rules.apply(stone)? {
+            let entry = next.entry(new_stone).or_insert(0);
+            *entry = counting.add(*entry, count)?;
+        }
+    }
+    Ok(next)
+}
+
 /// Evolve the sequence represented by a map of numbers and their counts
-/// for `n` iterations.
-fn evolve_sequence(initial: &HashMap<usize, usize>, n: usize) -> usize {
-    let mut current = initial.clone();
-    for _ in 0..n {
-        let mut next = HashMap::new();
-        for (num, count) in current {
-            apply_rules_to_map(num, count, &mut next);
+/// for `n` iterations. With a modulus the count is reduced, otherwise it errors
+/// if it does not fit.
+fn evolve_sequence(
+    initial: &HashMap<Stone, Count>,
+    n: usize,
+    rules: &RuleSet,
+    modulus: Option<u64>,
+) -> Result<Count, Box<dyn Error>> {
+    let counting = Counting::new(modulus)?;
+    let mut current: HashMap<Stone, Count> = initial
+        .iter()
+        .map(|(&stone, &count)| (stone, counting.reduce(count)))
+        .collect();
+    for i in 0..n {
+        current =
+            blink(&current, rules, counting).map_err(|e| format!("Blink {}: {}", i + 1, e))?;
+    }
+    let total = current
+        .values()
+        .try_fold(0, |acc, &count| counting.add(acc, count))
+        .map_err(|_| format!("Total stone count after {} blinks overflows", n))?;
+
+    Ok(total)
+}
+
+/// The number of distinct values on the stones, before blinking and after each blink.
+fn distinct_values_per_blink(
+    initial: &HashMap<Stone, Count>,
+    n: usize,
+    rules: &RuleSet,
+) -> Result<Vec<usize>, Box<dyn Error>> {
+    // Only the values matter here, not how many stones carry them
+    let mut current: HashSet<Stone> = initial.keys().copied().collect();
+    let mut distinct = vec![current.len()];
+    for i in 0..n {
+        let mut next = HashSet::new();
+        for &stone in &current {
+            let stones = rules
+                .apply(stone)
+                .map_err(|e| format!("Blink {}: {}", i + 1, e))?;
+            next.extend(stones);
         }
         current = next;
+        distinct.push(current.len());
+    }
+    Ok(distinct)
+}
+
+/// Largest set of stone values `find_closure` collects.
+const MAX_CLOSURE_SIZE: usize = 1_000_000;
+
+/// Largest set of stone values the dense transition matrix is built for.
+const MAX_MATRIX_SIZE: usize = 1000;
+
+/// Every stone value that can ever appear, starting from the initial stones.
+/// Blinking only ever produces values from this set.
+struct Closure {
+    values: Vec<Stone>,
+    index: HashMap<Stone, usize>,
+    /// Blinks until the last new value appeared
+    blinks: usize,
+}
+
+/// Blink the distinct values until no new ones appear. Errors if there are more than
+/// `limit` values, since some rule sets keep producing new numbers until they overflow.
+fn find_closure(
+    initial: &HashMap<Stone, Count>,
+    rules: &RuleSet,
+    limit: usize,
+) -> Result<Closure, Box<dyn Error>> {
+    let mut frontier: Vec<Stone> = initial.keys().copied().collect();
+    frontier.sort_unstable();
+    let mut values = frontier.clone();
+    let mut index: HashMap<Stone, usize> = frontier.iter().copied().zip(0..).collect();
+
+    let mut blinks = 0;
+    while !frontier.is_empty() {
+        let mut next = Vec::new();
+        for &stone in &frontier {
+            for new_stone in rules.apply(stone)? {
+                if let Entry::Vacant(entry) = index.entry(new_stone) {
+                    entry.insert(values.len());
+                    values.push(new_stone);
+                    next.push(new_stone);
+                }
+            }
+        }
+        if values.len() > limit {
+            return Err(format!("More than {} distinct stone values", limit).into());
+        }
+        if !next.is_empty() {
+            blinks += 1;
+        }
+        frontier = next;
+    }
+
+    Ok(Closure {
+        values,
+        index,
+        blinks,
+    })
+}
+
+/// Arithmetic on counts, either exact with overflow checks or modulo a number.
+/// The modulus fits in a u64, so the product of two reduced counts fits in a Count.
+#[derive(Clone, Copy)]
+struct Counting {
+    modulus: Option<u64>,
+}
+
+impl Counting {
+    fn new(modulus: Option<u64>) -> Result<Counting, Box<dyn Error>> {
+        if modulus == Some(0) {
+            return Err("Modulus must be positive".into());
+        }
+        Ok(Counting { modulus })
+    }
+
+    fn reduce(self, a: Count) -> Count {
+        match self.modulus {
+            Some(modulus) => a % modulus as Count,
+            None => a,
+        }
     }
-    let total: usize = current.values().sum();
 
-    total
+    fn add(self, a: Count, b: Count) -> Result<Count, Box<dyn Error>> {
+        let sum = a.checked_add(b).ok_or("Stone count overflows")?;
+        Ok(self.reduce(sum))
+    }
+
+    fn mul(self, a: Count, b: Count) -> Result<Count, Box<dyn Error>> {
+        let product = a.checked_mul(b).ok_or("Stone count overflows")?;
+        Ok(self.reduce(product))
+    }
+}
+
+/// A square matrix of counts, stored row by row.
+#[derive(Clone)]
+struct Matrix {
+    size: usize,
+    entries: Vec<Count>,
+}
+
+impl Matrix {
+    /// Entry `(i, j)` is how many stones with value `j` one stone with value `i` turns
+    /// into after a single blink.
+    fn transition(successors: &[Vec<usize>], counting: Counting) -> Result<Matrix, Box<dyn Error>> {
+        let size = successors.len();
+        let mut entries = vec![0; size * size];
+        for (i, row) in successors.iter().enumerate() {
+            for &j in row {
+                entries[i * size + j] = counting.add(entries[i * size + j], 1)?;
+            }
+        }
+        Ok(Matrix { size, entries })
+    }
+
+    fn multiply(&self, other: &Matrix, counting: Counting) -> Result<Matrix, Box<dyn Error>> {
+        let size = self.size;
+        let mut entries = vec![0; size * size];
+        for i in 0..size {
+            for k in 0..size {
+                let a = self.entries[i * size + k];
+                if a == 0 {
+                    continue;
+                }
+                for j in 0..size {
+                    let b = other.entries[k * size + j];
+                    if b != 0 {
+                        let product = counting.mul(a, b)?;
+                        entries[i * size + j] = counting.add(entries[i * size + j], product)?;
+                    }
+                }
+            }
+        }
+        Ok(Matrix { size, entries })
+    }
+
+    /// The matrix times a column vector.
+    fn apply(&self, vector: &[Count], counting: Counting) -> Result<Vec<Count>, Box<dyn Error>> {
+        let size = self.size;
+        (0..size)
+            .map(|i| {
+                (0..size).try_fold(0, |acc, j| {
+                    let product = counting.mul(self.entries[i * size + j], vector[j])?;
+                    counting.add(acc, product)
+                })
+            })
+            .collect()
+    }
+}
+
+/// For each value in the closure, the indices of the values it turns into after one blink.
+/// A stone turns into at most a couple of stones, so this is a very sparse transition.
+fn successors(closure: &Closure, rules: &RuleSet) -> Result<Vec<Vec<usize>>, Box<dyn Error>> {
+    closure
+        .values
+        .iter()
+        .map(|&stone| {
+            let stones = rules.apply(stone)?;
+            Ok(stones
+                .iter()
+                .map(|new_stone| closure.index[new_stone])
+                .collect())
+        })
+        .collect()
 }
 
-/// Apply the rules to a single number and add results to `out_map`.
-/// `count` is how many times `num` appears.
-fn apply_rules_to_map(num: usize, count: usize, out_map: &mut HashMap<usize, usize>) {
-    if num == 0 {
-        // Zero -> one
-        *out_map.entry(1).or_insert(0) += count;
+/// Whether raising the dense transition matrix to the `n`th power is cheaper than
+/// stepping through `n` blinks over the sparse transition. Each squaring is cubic in
+/// the number of values, while a step only follows every transition once.
+fn prefers_matrix(size: usize, transitions: usize, n: usize) -> bool {
+    if size > MAX_MATRIX_SIZE {
+        return false;
+    }
+    let squarings = (usize::BITS - n.leading_zeros()) as u128;
+    let matrix_cost = 2 * (size as u128).pow(3) * squarings;
+    let stepping_cost = n as u128 * transitions as u128;
+    matrix_cost < stepping_cost
+}
+
+/// Count the stones after `n` blinks over the closed set of values. Tracks how many
+/// stones one stone of each value turns into, either by stepping through the blinks
+/// on the sparse transition, or for very large `n` and few values by raising the
+/// transition matrix to the `n`th power, squaring for each bit of `n`. With a modulus
+/// the count is reduced, otherwise it errors if it does not fit.
+fn accelerated_count(
+    initial: &HashMap<Stone, Count>,
+    n: usize,
+    rules: &RuleSet,
+    modulus: Option<u64>,
+) -> Result<Count, Box<dyn Error>> {
+    let counting = Counting::new(modulus)?;
+    let closure = find_closure(initial, rules, MAX_CLOSURE_SIZE)?;
+    let successors = successors(&closure, rules)?;
+    let transitions = successors.iter().map(|row| row.len()).sum();
+
+    // How many stones one stone of each value turns into after the blinks so far
+    let mut stones = vec![counting.reduce(1); closure.values.len()];
+    if prefers_matrix(successors.len(), transitions, n) {
+        let mut power = Matrix::transition(&successors, counting)?;
+        let mut remaining = n;
+        while remaining > 0 {
+            if remaining & 1 == 1 {
+                stones = power.apply(&stones, counting)?;
+            }
+            remaining >>= 1;
+            if remaining > 0 {
+                power = power.multiply(&power, counting)?;
+            }
+        }
     } else {
-        let digits = num.checked_ilog10().unwrap_or(0) + 1;
-        if digits % 2 == 0 {
-            // Even number of digits -> split
-            let denominator = 10_u64.pow(digits / 2) as usize;
-            let (quotient, remainder) = (num / denominator, num % denominator);
-            *out_map.entry(quotient).or_insert(0) += count;
-            *out_map.entry(remainder).or_insert(0) += count;
-        } else {
-            // Odd digits and not zero -> multiply by 2024
-            let product = num * 2024;
-            *out_map.entry(product).or_insert(0) += count;
+        for i in 0..n {
+            stones = successors
+                .iter()
+                .map(|row| {
+                    row.iter()
+                        .try_fold(0, |acc, &j| counting.add(acc, stones[j]))
+                })
+                .collect::<Result<_, _>>()
+                .map_err(|e| format!("Blink {}: {}", i + 1, e))?;
         }
     }
+
+    initial.iter().try_fold(0, |acc, (stone, &count)| {
+        let product = counting.mul(counting.reduce(count), stones[closure.index[stone]])?;
+        counting.add(acc, product)
+    })
 }
 
 #[cfg(test)]
@@ -73,9 +467,129 @@ mod tests {
     #[test]
     fn test_part_one() {
         let initial_map = parse_input(TEST_DATA).unwrap();
-        let count = evolve_sequence(&initial_map, 25);
+        let count = evolve_sequence(&initial_map, 25, &RuleSet::standard(), None).unwrap();
 
         let expected_count = 55312;
         assert_eq!(count, expected_count);
     }
+
+    #[test]
+    fn test_parse_rules() {
+        let rules = RuleSet::parse("0->1, split, *2024").unwrap();
+        assert_eq!(rules, RuleSet::standard());
+
+        assert!(RuleSet::parse("0->1, halve").is_err());
+    }
+
+    #[test]
+    fn test_variant_rules() {
+        let initial_map = parse_input("0 7").unwrap();
+
+        // Without a catch-all rule the 7 is stuck
+        let rules = RuleSet::parse("0->1, split").unwrap();
+        let error = evolve_sequence(&initial_map, 1, &rules, None).unwrap_err();
+        assert_eq!(error.to_string(), "Blink 1: No rule applies to stone 7");
+
+        // 0 7 -> 1 28 -> 1 2 8 -> 1 8 32 -> 1 32 3 2
+        let rules = RuleSet::parse("0->1, 1->1, split, *4").unwrap();
+        let count = evolve_sequence(&initial_map, 4, &rules, None).unwrap();
+        assert_eq!(count, 4);
+    }
+
+    #[test]
+    fn test_overflow_is_an_error() {
+        // 1 -> 10 -> 100 -> ... until the number no longer fits on a stone
+        let initial_map = parse_input("1").unwrap();
+        let rules = RuleSet::parse("*10").unwrap();
+        let error = evolve_sequence(&initial_map, 25, &rules, None).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Blink 20: Stone 10000000000000000000 times 10 overflows"
+        );
+
+        // Counts go past a u64 long before they run out of room in a u128
+        let initial_map = parse_input(TEST_DATA).unwrap();
+        let count = evolve_sequence(&initial_map, 200, &RuleSet::standard(), None).unwrap();
+        assert!(count > u64::MAX as Count);
+
+        let error = evolve_sequence(&initial_map, 300, &RuleSet::standard(), None).unwrap_err();
+        assert!(error.to_string().ends_with("Stone count overflows"));
+    }
+
+    #[test]
+    fn test_distinct_values() {
+        // 125 17 -> 253000 1 7 -> 253 0 2024 14168 -> 512072 1 20 24 28676032
+        //        -> 512 72 2024 2 0 2 4 2867 6032
+        let initial_map = parse_input(TEST_DATA).unwrap();
+        let distinct = distinct_values_per_blink(&initial_map, 4, &RuleSet::standard()).unwrap();
+        assert_eq!(distinct, vec![2, 3, 4, 5, 8]);
+    }
+
+    #[test]
+    fn test_closure() {
+        let initial_map = parse_input(TEST_DATA).unwrap();
+        let rules = RuleSet::parse("0->1, split, *2").unwrap();
+        let closure = find_closure(&initial_map, &rules, MAX_CLOSURE_SIZE).unwrap();
+
+        let mut values = closure.values.clone();
+        values.sort_unstable();
+        assert_eq!(
+            values,
+            vec![0, 1, 2, 4, 6, 7, 8, 10, 12, 14, 16, 17, 125, 250, 500, 1000]
+        );
+        assert_eq!(closure.blinks, 7);
+
+        // Multiplying without splitting never closes
+        let rules = RuleSet::parse("*3").unwrap();
+        assert!(find_closure(&initial_map, &rules, 100).is_err());
+    }
+
+    #[test]
+    fn test_accelerated_count() {
+        let initial_map = parse_input(TEST_DATA).unwrap();
+        let rules = RuleSet::parse("0->1, split, *2").unwrap();
+        for n in [0, 1, 2, 7, 25, 64, 100] {
+            let count = accelerated_count(&initial_map, n, &rules, None).unwrap();
+            assert_eq!(
+                count,
+                evolve_sequence(&initial_map, n, &rules, None).unwrap()
+            );
+        }
+
+        // Only 16 values, so the matrix takes over for many blinks
+        let modulus = 1_000_000_007;
+        assert!(!prefers_matrix(16, 20, 100));
+        assert!(prefers_matrix(16, 20, 10_000));
+        let reduced = accelerated_count(&initial_map, 10_000, &rules, Some(modulus)).unwrap();
+        let expected = evolve_sequence(&initial_map, 10_000, &rules, Some(modulus)).unwrap();
+        assert_eq!(reduced, expected);
+
+        // Far too many stones to count exactly, but fine modulo a prime
+        assert!(accelerated_count(&initial_map, 10_000, &rules, None).is_err());
+        let reduced =
+            accelerated_count(&initial_map, 1_000_000_000_000, &rules, Some(modulus)).unwrap();
+        assert!(reduced < modulus as Count);
+
+        assert!(accelerated_count(&initial_map, 10, &rules, Some(0)).is_err());
+    }
+
+    #[test]
+    fn test_accelerated_count_standard_rules() {
+        // These stones close on thousands of values, like a real puzzle input
+        let initial_map = parse_input("0 1 10 99 999 125 9 17 2024 8675309").unwrap();
+        let rules = RuleSet::standard();
+        let closure = find_closure(&initial_map, &rules, MAX_CLOSURE_SIZE).unwrap();
+        assert_eq!(closure.values.len(), 3888);
+
+        let count = accelerated_count(&initial_map, 75, &rules, None).unwrap();
+        assert_eq!(
+            count,
+            evolve_sequence(&initial_map, 75, &rules, None).unwrap()
+        );
+
+        let modulus = 1_000_000_007;
+        let reduced = accelerated_count(&initial_map, 200, &rules, Some(modulus)).unwrap();
+        let expected = evolve_sequence(&initial_map, 200, &rules, Some(modulus)).unwrap();
+        assert_eq!(reduced, expected);
+    }
 }
@@ -187,7 +187,7 @@ fn number_of_sequences(array: &[usize]) -> usize {
 }
 
 // Counts the sides as contigous sequences on the same row/col with identical direction
-fn count_sides(edges: &Vec<Edge>, rows: usize, cols: usize) -> usize {
+fn count_sides(edges: &[Edge], rows: usize, cols: usize) -> usize {
     let mut sides = 0;
 
     for &direction in &VERTICAL_DIRECTIONS {
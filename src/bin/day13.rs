@@ -9,7 +9,7 @@ fn main() -> Result<(), Box<dyn Error>> {
 
     let mut total_tokens_part_one = 0;
     for item in items.iter() {
-        if let Some(tokens) = solve_item(&item) {
+        if let Some(tokens) = solve_item(item) {
             total_tokens_part_one += tokens;
         }
     }
@@ -21,7 +21,7 @@ fn main() -> Result<(), Box<dyn Error>> {
     for item in items.iter_mut() {
         item.xp += offset;
         item.yp += offset;
-        if let Some(tokens) = solve_item(&item) {
+        if let Some(tokens) = solve_item(item) {
             total_tokens_part_two += tokens;
         }
     }
@@ -209,7 +209,7 @@ Prize: X=18641, Y=10279";
         for (i, item) in items.iter_mut().enumerate() {
             item.xp += offset;
             item.yp += offset;
-            if solve_item(&item).is_some() {
+            if solve_item(item).is_some() {
                 claws.push(i + 1)
             }
         }
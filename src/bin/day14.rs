@@ -21,26 +21,28 @@ fn main() -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
-fn parse_input(input: &str) -> Result<Vec<(i64, i64, i64, i64)>, Box<dyn Error>> {
+type Robot = (i64, i64, i64, i64);
+
+fn parse_input(input: &str) -> Result<Vec<Robot>, Box<dyn Error>> {
     let mut robots = Vec::new();
     for line in input.lines() {
         if line.trim().is_empty() {
             continue;
         }
-        let (x, y, vx, vy) = parse_line(&line)?;
+        let (x, y, vx, vy) = parse_line(line)?;
         robots.push((x, y, vx, vy));
     }
     Ok(robots)
 }
 
-fn simulate(robots: &mut Vec<(i64, i64, i64, i64)>, width: i64, height: i64, seconds: i64) {
+fn simulate(robots: &mut [Robot], width: i64, height: i64, seconds: i64) {
     for robot in robots.iter_mut() {
         robot.0 = mod_wrap(robot.0 + seconds * robot.2, width);
         robot.1 = mod_wrap(robot.1 + seconds * robot.3, height);
     }
 }
 
-fn compute_score(robots: &Vec<(i64, i64, i64, i64)>, width: i64, height: i64) -> i64 {
+fn compute_score(robots: &[Robot], width: i64, height: i64) -> i64 {
     // Count quadrants
     let mut q_tl = 0;
     let mut q_tr = 0;
@@ -64,16 +66,10 @@ fn compute_score(robots: &Vec<(i64, i64, i64, i64)>, width: i64, height: i64) ->
         }
     }
 
-    let final_score = q_tl as i64 * q_tr as i64 * q_bl as i64 * q_br as i64;
-
-    final_score
+    q_tl as i64 * q_tr as i64 * q_bl as i64 * q_br as i64
 }
 
-fn search_for_christmas_tree(
-    robots: &mut Vec<(i64, i64, i64, i64)>,
-    width: i64,
-    height: i64,
-) -> i64 {
+fn search_for_christmas_tree(robots: &mut [Robot], width: i64, height: i64) -> i64 {
     let mut seconds = 0;
     loop {
         seconds += 1;
@@ -121,7 +117,7 @@ fn wait_for_key() {
 fn parse_line(line: &str) -> Result<(i64, i64, i64, i64), Box<dyn Error>> {
     // Format: "p=62,20 v=85,-14"
     // Split by spaces
-    let parts: Vec<&str> = line.trim().split_whitespace().collect();
+    let parts: Vec<&str> = line.split_whitespace().collect();
     // parts[0]: "p=62,20"
     // parts[1]: "v=85,-14"
 
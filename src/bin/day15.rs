@@ -30,7 +30,7 @@ enum Direction {
 }
 
 impl Direction {
-    fn to_offset(&self) -> (isize, isize) {
+    fn to_offset(self) -> (isize, isize) {
         match self {
             Direction::Left => (0, -1),
             Direction::Right => (0, 1),
@@ -73,7 +73,7 @@ impl Cell {
         }
     }
 
-    fn to_char(&self) -> char {
+    fn to_char(self) -> char {
         match self {
             Cell::Empty => '.',
             Cell::Wall => '#',
@@ -107,8 +107,8 @@ impl Map {
         let cols = self.grid[0].len();
         let mut widened = vec![vec![Cell::Empty; cols * 2]; rows];
 
-        for r in 0..rows {
-            for (c, &cell) in self.grid[r].iter().enumerate() {
+        for (r, row) in self.grid.iter().enumerate() {
+            for (c, &cell) in row.iter().enumerate() {
                 let (c1, c2) = match cell {
                     Cell::Wall => (Cell::Wall, Cell::Wall),
                     Cell::Empty => (Cell::Empty, Cell::Empty),
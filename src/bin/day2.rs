@@ -1,5 +1,7 @@
 use aoc2024::fetch_or_load_input;
+use std::env;
 use std::error::Error;
+use std::fmt;
 
 fn main() -> Result<(), Box<dyn Error>> {
     let day = 2;
@@ -14,31 +16,224 @@ fn main() -> Result<(), Box<dyn Error>> {
         number_of_safe_reports
     );
 
+    let args: Vec<String> = env::args().collect();
+
+    // e.g. `day2 --max-removals 2 --max-step 4 --increasing` counts with a custom policy
+    let custom_policy = SafetyPolicy::from_args(&args)?;
+    if let Some(policy) = &custom_policy {
+        let number_of_safe_reports = calculate_safe_reports(&input, policy)?;
+        println!(
+            "Number of safe reports with {:?}: {}",
+            policy, number_of_safe_reports
+        );
+    }
+
+    // `day2 --explain` lists the verdict for every report, under the custom policy if given
+    let policy = custom_policy.unwrap_or(SafetyPolicy::PART_TWO);
+    if args.iter().any(|arg| arg == "--explain") {
+        for (line_number, row) in parse_input(&input)?.iter().enumerate() {
+            let levels: Vec<String> = row.iter().map(|level| level.to_string()).collect();
+            println!(
+                "Report {} [{}]: {}",
+                line_number + 1,
+                levels.join(" "),
+                explain_report(row, &policy)
+            );
+        }
+    }
+
     Ok(())
 }
 
 fn calculate_safe_reports_part_one(input: &str) -> Result<usize, Box<dyn Error>> {
-    let rows = parse_input(input)?;
-    let valid_count = rows
-        .iter()
-        .map(|row| is_valid_sequence(row))
-        .filter(|&is_valid| is_valid)
-        .count();
-
-    Ok(valid_count)
+    calculate_safe_reports(input, &SafetyPolicy::PART_ONE)
 }
 
 fn calculate_safe_reports_part_two(input: &str) -> Result<usize, Box<dyn Error>> {
+    calculate_safe_reports(input, &SafetyPolicy::PART_TWO)
+}
+
+fn calculate_safe_reports(input: &str, policy: &SafetyPolicy) -> Result<usize, Box<dyn Error>> {
     let rows = parse_input(input)?;
-    let valid_count = rows
-        .iter()
-        .map(|row| is_valid_or_can_be_made_valid(row))
-        .filter(|&is_valid| is_valid)
-        .count();
+    let valid_count = rows.iter().filter(|row| policy.is_safe(row)).count();
 
     Ok(valid_count)
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Trend {
+    Any,
+    Increasing,
+    Decreasing,
+}
+
+/// The rules a report has to follow to be considered safe.
+#[derive(Debug, Clone, Copy)]
+struct SafetyPolicy {
+    /// Smallest allowed non-zero step, in the direction of the trend
+    min_step: i32,
+    /// Largest allowed step, in the direction of the trend
+    max_step: i32,
+    /// Whether two equal neighbouring levels are allowed
+    allow_plateaus: bool,
+    trend: Trend,
+    /// How many levels may be removed to make the report safe
+    max_removals: usize,
+}
+
+impl SafetyPolicy {
+    const PART_ONE: SafetyPolicy = SafetyPolicy {
+        min_step: 1,
+        max_step: 3,
+        allow_plateaus: false,
+        trend: Trend::Any,
+        max_removals: 0,
+    };
+
+    const PART_TWO: SafetyPolicy = SafetyPolicy {
+        max_removals: 1,
+        ..Self::PART_ONE
+    };
+
+    /// Build a policy from command line flags on top of the part one rules.
+    /// Returns None if no policy flag is given.
+    fn from_args(args: &[String]) -> Result<Option<SafetyPolicy>, Box<dyn Error>> {
+        let mut policy = Self::PART_ONE;
+        let mut customized = false;
+
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            let mut value = || iter.next().ok_or(format!("Missing value after {}", arg));
+            match arg.as_str() {
+                "--min-step" => policy.min_step = value()?.parse()?,
+                "--max-step" => policy.max_step = value()?.parse()?,
+                "--max-removals" => policy.max_removals = value()?.parse()?,
+                "--plateaus" => policy.allow_plateaus = true,
+                "--increasing" => policy.trend = Trend::Increasing,
+                "--decreasing" => policy.trend = Trend::Decreasing,
+                _ => continue,
+            }
+            customized = true;
+        }
+
+        Ok(customized.then_some(policy))
+    }
+
+    fn is_safe(&self, row: &[i32]) -> bool {
+        self.removals_needed(row).is_some()
+    }
+
+    /// The fewest levels that must be removed to make the report safe,
+    /// or None if that takes more than `max_removals`.
+    fn removals_needed(&self, row: &[i32]) -> Option<usize> {
+        self.removal_plan(row).map(|removed| removed.len())
+    }
+
+    /// The indices of the levels to remove to make the report safe, as few as possible
+    /// and then the lowest ones, or None if that takes more than `max_removals`.
+    fn removal_plan(&self, row: &[i32]) -> Option<Vec<usize>> {
+        let plans = match self.trend {
+            Trend::Increasing => vec![self.removal_plan_for(row, true)],
+            Trend::Decreasing => vec![self.removal_plan_for(row, false)],
+            Trend::Any => vec![
+                self.removal_plan_for(row, true),
+                self.removal_plan_for(row, false),
+            ],
+        };
+        plans
+            .into_iter()
+            .flatten()
+            .min_by(|a, b| (a.len(), a).cmp(&(b.len(), b)))
+    }
+
+    /// Dynamic programming over the kept levels. `removed[i]` is the best set of removed
+    /// levels among 0..=i such that level i is kept and the kept levels so far are valid.
+    /// Only the previous k + 1 levels can precede a kept level, so each level has at most
+    /// k + 1 candidates.
+    fn removal_plan_for(&self, row: &[i32], increasing: bool) -> Option<Vec<usize>> {
+        let n = row.len();
+        if n == 0 {
+            return Some(Vec::new());
+        }
+
+        let k = self.max_removals;
+        let is_better = |candidate: &Vec<usize>, best: &Option<Vec<usize>>| {
+            best.as_ref()
+                .is_none_or(|b| (candidate.len(), candidate) < (b.len(), b))
+        };
+        let mut removed: Vec<Option<Vec<usize>>> = vec![None; n];
+
+        for i in 0..n {
+            // Keep level i as the first level, removing everything before it
+            let mut best = (i <= k).then(|| (0..i).collect());
+
+            for j in i.saturating_sub(k + 1)..i {
+                let Some(before) = &removed[j] else {
+                    continue;
+                };
+                if before.len() + (i - j - 1) > k || !self.is_valid_step(row[j], row[i], increasing)
+                {
+                    continue;
+                }
+                let candidate = before.iter().copied().chain(j + 1..i).collect();
+                if is_better(&candidate, &best) {
+                    best = Some(candidate);
+                }
+            }
+
+            removed[i] = best;
+        }
+
+        // Keep level i as the last level, removing everything after it
+        let mut best = None;
+        let last_kept = n.saturating_sub(k + 1);
+        for (i, before) in removed.iter().enumerate().skip(last_kept) {
+            if let Some(before) = before {
+                let candidate: Vec<usize> = before.iter().copied().chain(i + 1..n).collect();
+                if candidate.len() <= k && is_better(&candidate, &best) {
+                    best = Some(candidate);
+                }
+            }
+        }
+        best
+    }
+
+    /// The first step of the report that breaks the rules when no levels are removed.
+    fn first_failure(&self, row: &[i32]) -> Option<Failure> {
+        let increasing = match self.trend {
+            Trend::Increasing => true,
+            Trend::Decreasing => false,
+            // The first step that is not a plateau sets the trend
+            Trend::Any => row
+                .windows(2)
+                .find(|window| window[0] != window[1])
+                .is_none_or(|window| window[1] > window[0]),
+        };
+
+        let (position, window) = row
+            .windows(2)
+            .enumerate()
+            .find(|(_, window)| !self.is_valid_step(window[0], window[1], increasing))?;
+        let step = window[1] - window[0];
+        Some(if step == 0 {
+            Failure::ZeroStep { position }
+        } else if !(self.min_step..=self.max_step).contains(&step.abs()) {
+            Failure::BadStepSize { position, step }
+        } else {
+            Failure::TrendFlip { position }
+        })
+    }
+
+    fn is_valid_step(&self, from: i32, to: i32, increasing: bool) -> bool {
+        let step = if increasing { to - from } else { from - to };
+        if step == 0 {
+            self.allow_plateaus
+        } else {
+            (self.min_step..=self.max_step).contains(&step)
+        }
+    }
+}
+
 fn parse_input(input: &str) -> Result<Vec<Vec<i32>>, Box<dyn Error>> {
     let mut rows = Vec::new();
 
@@ -58,60 +253,61 @@ fn parse_input(input: &str) -> Result<Vec<Vec<i32>>, Box<dyn Error>> {
     Ok(rows)
 }
 
-/// Determines if a sequence is valid or can be made valid by removing one element.
-fn is_valid_or_can_be_made_valid(row: &[i32]) -> bool {
-    // First, check if the original sequence is valid
-    if is_valid_sequence(row) {
-        return true;
-    }
+/// Why a report is unsafe. `position` is the index of the step, i.e. the step
+/// between levels `position` and `position + 1`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Failure {
+    ZeroStep { position: usize },
+    BadStepSize { position: usize, step: i32 },
+    TrendFlip { position: usize },
+}
 
-    // Try removing each element one at a time
-    for i in 0..row.len() {
-        let mut modified_row = row.to_vec();
-        modified_row.remove(i);
-        if is_valid_sequence(&modified_row) {
-            return true;
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Failure::ZeroStep { position } => write!(f, "zero step at position {}", position),
+            Failure::BadStepSize { position, step } => {
+                write!(f, "step of {} at position {}", step, position)
+            }
+            Failure::TrendFlip { position } => write!(f, "trend flips at position {}", position),
         }
     }
-
-    // Sequence cannot be made valid by removing one element
-    false
 }
 
-/// Checks if a sequence is strictly increasing or decreasing with valid differences.
-fn is_valid_sequence(row: &[i32]) -> bool {
-    if row.len() < 2 {
-        return true;
-    }
-
-    let mut trend = None; // None: trend not determined yet; Some(true): increasing; Some(false): decreasing
-
-    for window in row.windows(2) {
-        let diff = window[1] - window[0];
-
-        if diff == 0 {
-            return false; // Equal values are not allowed
-        }
-
-        let abs_diff = diff.abs();
-
-        // Check if the absolute difference is between 1 and 3
-        if abs_diff < 1 || abs_diff > 3 {
-            return false;
-        }
-
-        let current_trend = diff > 0; // True for increasing, false for decreasing
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Verdict {
+    Safe,
+    SafeByRemoving(Vec<usize>),
+    Unsafe(Failure),
+}
 
-        if let Some(t) = trend {
-            if current_trend != t {
-                return false; // Trend changed
+impl fmt::Display for Verdict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Verdict::Safe => write!(f, "safe"),
+            Verdict::SafeByRemoving(removed) if removed.len() == 1 => {
+                write!(f, "safe by removing level {}", removed[0])
             }
-        } else {
-            trend = Some(current_trend); // Set the initial trend
+            Verdict::SafeByRemoving(removed) => {
+                let levels: Vec<String> = removed.iter().map(|index| index.to_string()).collect();
+                write!(f, "safe by removing levels {}", levels.join(", "))
+            }
+            Verdict::Unsafe(failure) => write!(f, "unsafe, {}", failure),
         }
     }
+}
+
+/// Determines if a report is safe as is, which levels to remove to make it safe,
+/// or why it fails under the given policy.
+fn explain_report(row: &[i32], policy: &SafetyPolicy) -> Verdict {
+    let Some(failure) = policy.first_failure(row) else {
+        return Verdict::Safe;
+    };
 
-    true
+    match policy.removal_plan(row) {
+        Some(removed) => Verdict::SafeByRemoving(removed),
+        None => Verdict::Unsafe(failure),
+    }
 }
 
 #[cfg(test)]
@@ -137,4 +333,172 @@ mod tests {
         let result = calculate_safe_reports_part_two(TEST_DATA).unwrap();
         assert_eq!(result, 4);
     }
+
+    #[test]
+    fn test_explain_report() {
+        let rows = parse_input(TEST_DATA).unwrap();
+        let verdicts: Vec<Verdict> = rows
+            .iter()
+            .map(|row| explain_report(row, &SafetyPolicy::PART_TWO))
+            .collect();
+
+        let expected_verdicts = [
+            Verdict::Safe,
+            Verdict::Unsafe(Failure::BadStepSize {
+                position: 1,
+                step: 5,
+            }),
+            Verdict::Unsafe(Failure::BadStepSize {
+                position: 2,
+                step: -4,
+            }),
+            Verdict::SafeByRemoving(vec![1]),
+            Verdict::SafeByRemoving(vec![2]),
+            Verdict::Safe,
+        ];
+        assert_eq!(verdicts, expected_verdicts);
+    }
+
+    #[test]
+    fn test_explain_report_follows_policy() {
+        // A step of 4 is allowed, so the third example report becomes safe
+        let policy = SafetyPolicy {
+            max_step: 4,
+            ..SafetyPolicy::PART_TWO
+        };
+        assert_eq!(explain_report(&[9, 7, 6, 2, 1], &policy), Verdict::Safe);
+
+        let policy = SafetyPolicy {
+            max_removals: 2,
+            ..SafetyPolicy::PART_ONE
+        };
+        let verdict = explain_report(&[1, 9, 2, 9, 3, 4], &policy);
+        assert_eq!(verdict, Verdict::SafeByRemoving(vec![1, 3]));
+        assert_eq!(verdict.to_string(), "safe by removing levels 1, 3");
+
+        // Plateaus are fine, but the trend may not flip
+        let policy = SafetyPolicy {
+            allow_plateaus: true,
+            ..SafetyPolicy::PART_ONE
+        };
+        assert_eq!(
+            explain_report(&[1, 1, 2, 1], &policy),
+            Verdict::Unsafe(Failure::TrendFlip { position: 2 })
+        );
+        assert_eq!(explain_report(&[4, 4, 2, 2], &policy), Verdict::Safe);
+
+        let policy = SafetyPolicy {
+            min_step: 2,
+            ..SafetyPolicy::PART_ONE
+        };
+        assert_eq!(
+            explain_report(&[1, 3, 4], &policy),
+            Verdict::Unsafe(Failure::BadStepSize {
+                position: 1,
+                step: 1
+            })
+        );
+    }
+
+    #[test]
+    fn test_explain_report_matches_brute_force() {
+        let rows: [&[i32]; 6] = [
+            &[1, 5, 6, 7],
+            &[5, 1, 2, 3],
+            &[1, 2, 3, 9],
+            &[3, 1, 2, 3, 4],
+            &[1, 2, 2, 2],
+            &[4, 5, 4, 3, 2],
+        ];
+        let policy = SafetyPolicy::PART_TWO;
+        for row in rows {
+            let brute_force = (0..row.len()).find(|&i| {
+                let mut modified_row = row.to_vec();
+                modified_row.remove(i);
+                policy.first_failure(&modified_row).is_none()
+            });
+            let expected = match brute_force {
+                _ if policy.first_failure(row).is_none() => Verdict::Safe,
+                Some(i) => Verdict::SafeByRemoving(vec![i]),
+                None => Verdict::Unsafe(policy.first_failure(row).unwrap()),
+            };
+            assert_eq!(explain_report(row, &policy), expected, "row {:?}", row);
+        }
+    }
+
+    /// Fewest removals found by trying every subset of up to `max_removals` levels.
+    fn brute_force_removals(policy: &SafetyPolicy, row: &[i32]) -> Option<usize> {
+        let strict = SafetyPolicy {
+            max_removals: 0,
+            ..*policy
+        };
+        (0u32..1 << row.len())
+            .filter(|mask| mask.count_ones() as usize <= policy.max_removals)
+            .filter(|mask| {
+                let kept: Vec<i32> = (0..row.len())
+                    .filter(|&i| mask & (1 << i) == 0)
+                    .map(|i| row[i])
+                    .collect();
+                strict.removals_needed(&kept) == Some(0)
+            })
+            .map(|mask| mask.count_ones() as usize)
+            .min()
+    }
+
+    #[test]
+    fn test_removals_needed_matches_brute_force() {
+        let rows = parse_input(TEST_DATA).unwrap();
+        let extra_rows = [
+            vec![1, 9, 2, 9, 3, 4],
+            vec![10, 1, 9, 8, 20, 7, 6],
+            vec![5, 5, 6, 6, 7, 1],
+            vec![1, 2, 3, 2, 1],
+        ];
+
+        let plateaus = SafetyPolicy {
+            allow_plateaus: true,
+            trend: Trend::Increasing,
+            max_removals: 2,
+            ..SafetyPolicy::PART_ONE
+        };
+        let wide_steps = SafetyPolicy {
+            min_step: 2,
+            max_step: 10,
+            trend: Trend::Decreasing,
+            max_removals: 3,
+            ..SafetyPolicy::PART_ONE
+        };
+        let policies = [
+            SafetyPolicy::PART_ONE,
+            SafetyPolicy::PART_TWO,
+            SafetyPolicy {
+                max_removals: 2,
+                ..SafetyPolicy::PART_ONE
+            },
+            plateaus,
+            wide_steps,
+        ];
+
+        for policy in &policies {
+            for row in rows.iter().chain(extra_rows.iter()) {
+                assert_eq!(
+                    policy.removals_needed(row),
+                    brute_force_removals(policy, row),
+                    "policy {:?}, row {:?}",
+                    policy,
+                    row
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_two_removals() {
+        let policy = SafetyPolicy {
+            max_removals: 2,
+            ..SafetyPolicy::PART_ONE
+        };
+        assert_eq!(policy.removals_needed(&[1, 9, 2, 9, 3, 4]), Some(2));
+        assert_eq!(calculate_safe_reports(TEST_DATA, &policy).unwrap(), 6);
+    }
 }
@@ -0,0 +1,215 @@
+use aoc2024::fetch_or_load_input;
+use std::collections::HashMap;
+use std::error::Error;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let day = 21;
+    let input = fetch_or_load_input(day)?;
+
+    let codes = parse_input(&input);
+    let mut chain = KeypadChain::new();
+
+    let complexity_part_one = total_complexity(&mut chain, &codes, 2)?;
+    println!("Total complexity part one: {}", complexity_part_one);
+
+    let complexity_part_two = total_complexity(&mut chain, &codes, 25)?;
+    println!("Total complexity part two: {}", complexity_part_two);
+
+    Ok(())
+}
+
+fn parse_input(input: &str) -> Vec<String> {
+    input
+        .lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty())
+        .map(|line| line.to_string())
+        .collect()
+}
+
+/// A keypad layout: the (row, col) of every key plus the gap the arm must never point at.
+struct Keypad {
+    positions: HashMap<char, (isize, isize)>,
+    gap: (isize, isize),
+}
+
+impl Keypad {
+    fn from_rows(rows: &[&str]) -> Self {
+        let mut positions = HashMap::new();
+        let mut gap = (0, 0);
+        for (r, row) in rows.iter().enumerate() {
+            for (c, key) in row.chars().enumerate() {
+                if key == ' ' {
+                    gap = (r as isize, c as isize);
+                } else {
+                    positions.insert(key, (r as isize, c as isize));
+                }
+            }
+        }
+        Keypad { positions, gap }
+    }
+
+    fn numeric() -> Self {
+        Self::from_rows(&["789", "456", "123", " 0A"])
+    }
+
+    fn directional() -> Self {
+        Self::from_rows(&[" ^A", "<v>"])
+    }
+
+    fn position(&self, key: char) -> Result<(isize, isize), Box<dyn Error>> {
+        self.positions
+            .get(&key)
+            .copied()
+            .ok_or_else(|| format!("Invalid key: '{}'", key).into())
+    }
+
+    /// Candidate button sequences (ending with 'A') that move the arm from `from` to `to`
+    /// and press it. Only the two straight-line orders are worth considering, since
+    /// repeated presses of the same button are free one level up.
+    fn paths(&self, from: char, to: char) -> Result<Vec<String>, Box<dyn Error>> {
+        let (r1, c1) = self.position(from)?;
+        let (r2, c2) = self.position(to)?;
+
+        let horizontal_key = if c2 > c1 { '>' } else { '<' };
+        let vertical_key = if r2 > r1 { 'v' } else { '^' };
+        let horizontal: String = std::iter::repeat_n(horizontal_key, c1.abs_diff(c2)).collect();
+        let vertical: String = std::iter::repeat_n(vertical_key, r1.abs_diff(r2)).collect();
+
+        let mut paths = Vec::new();
+        // Horizontal first passes through the corner (r1, c2)
+        if (r1, c2) != self.gap {
+            paths.push(format!("{}{}A", horizontal, vertical));
+        }
+        // Vertical first passes through the corner (r2, c1)
+        if (r2, c1) != self.gap {
+            let path = format!("{}{}A", vertical, horizontal);
+            if !paths.contains(&path) {
+                paths.push(path);
+            }
+        }
+
+        Ok(paths)
+    }
+}
+
+/// A numeric keypad driven through a chain of directional keypads.
+/// Costs are counted in human button presses and memoized per (from, to, depth).
+struct KeypadChain {
+    numeric: Keypad,
+    directional: Keypad,
+    memo: HashMap<(char, char, usize), usize>,
+}
+
+impl KeypadChain {
+    fn new() -> Self {
+        KeypadChain {
+            numeric: Keypad::numeric(),
+            directional: Keypad::directional(),
+            memo: HashMap::new(),
+        }
+    }
+
+    /// Number of human presses needed to type `code` on the numeric keypad
+    /// when `robots` directional keypads sit between the human and the numeric robot.
+    fn code_length(&mut self, code: &str, robots: usize) -> Result<usize, Box<dyn Error>> {
+        let mut total = 0;
+        let mut from = 'A';
+        for to in code.chars() {
+            let mut best = usize::MAX;
+            for path in self.numeric.paths(from, to)? {
+                best = best.min(self.sequence_cost(&path, robots)?);
+            }
+            total += best;
+            from = to;
+        }
+        Ok(total)
+    }
+
+    /// Cost of pressing `sequence` on a directional keypad at the given depth,
+    /// starting with the arm on 'A'.
+    fn sequence_cost(&mut self, sequence: &str, depth: usize) -> Result<usize, Box<dyn Error>> {
+        let mut total = 0;
+        let mut from = 'A';
+        for to in sequence.chars() {
+            total += self.move_cost(from, to, depth)?;
+            from = to;
+        }
+        Ok(total)
+    }
+
+    /// Cost of moving a directional keypad arm from `from` to `to` and pressing it.
+    /// Depth 0 is the keypad the human presses directly.
+    fn move_cost(&mut self, from: char, to: char, depth: usize) -> Result<usize, Box<dyn Error>> {
+        if depth == 0 {
+            return Ok(1);
+        }
+        if let Some(&cost) = self.memo.get(&(from, to, depth)) {
+            return Ok(cost);
+        }
+
+        let mut best = usize::MAX;
+        for path in self.directional.paths(from, to)? {
+            best = best.min(self.sequence_cost(&path, depth - 1)?);
+        }
+
+        self.memo.insert((from, to, depth), best);
+        Ok(best)
+    }
+}
+
+/// Sum of sequence length times the numeric part of each code.
+fn total_complexity(
+    chain: &mut KeypadChain,
+    codes: &[String],
+    robots: usize,
+) -> Result<usize, Box<dyn Error>> {
+    let mut total = 0;
+    for code in codes {
+        let length = chain.code_length(code, robots)?;
+        let numeric_part: usize = code.trim_end_matches('A').parse()?;
+        total += length * numeric_part;
+    }
+    Ok(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_DATA: &str = "\
+029A
+980A
+179A
+456A
+379A";
+
+    #[test]
+    fn test_code_length() {
+        let mut chain = KeypadChain::new();
+        let length = chain.code_length("029A", 2).unwrap();
+
+        let expected_length = 68;
+        assert_eq!(length, expected_length);
+    }
+
+    #[test]
+    fn test_part_one() {
+        let codes = parse_input(TEST_DATA);
+        let mut chain = KeypadChain::new();
+        let complexity = total_complexity(&mut chain, &codes, 2).unwrap();
+
+        let expected_complexity = 126384;
+        assert_eq!(complexity, expected_complexity);
+    }
+
+    #[test]
+    fn test_part_two() {
+        let codes = parse_input(TEST_DATA);
+        let mut chain = KeypadChain::new();
+        let complexity = total_complexity(&mut chain, &codes, 25).unwrap();
+
+        let expected_complexity = 154115708116294;
+        assert_eq!(complexity, expected_complexity);
+    }
+}
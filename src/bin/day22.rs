@@ -0,0 +1,132 @@
+use aoc2024::fetch_or_load_input;
+use std::error::Error;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let day = 22;
+    let input = fetch_or_load_input(day)?;
+
+    let secrets = parse_input(&input)?;
+
+    let sum = sum_of_secrets(&secrets, 2000);
+    println!("Sum of 2000th secrets part one: {}", sum);
+
+    let bananas = most_bananas(&secrets, 2000);
+    println!("Most bananas part two: {}", bananas);
+
+    Ok(())
+}
+
+fn parse_input(input: &str) -> Result<Vec<u64>, Box<dyn Error>> {
+    let mut secrets = Vec::new();
+    for line in input.lines() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        secrets.push(line.parse()?);
+    }
+    Ok(secrets)
+}
+
+const PRUNE_MODULUS: u64 = 16777216;
+
+/// Number of distinct price changes: -9..=9
+const DELTA_RANGE: usize = 19;
+
+/// Number of distinct four-change sequences, i.e. the size of the dense lookup table
+const SEQUENCE_COUNT: usize = DELTA_RANGE * DELTA_RANGE * DELTA_RANGE * DELTA_RANGE;
+
+/// Advance a secret number one step using the mix/prune rules.
+/// The prune modulus is a power of two, so only the low bits of the product matter
+/// and a multiplication that wraps for huge initial secrets gives the same result.
+fn next_secret(secret: u64) -> u64 {
+    let secret = (secret.wrapping_mul(64) ^ secret) % PRUNE_MODULUS;
+    let secret = ((secret / 32) ^ secret) % PRUNE_MODULUS;
+    ((secret * 2048) ^ secret) % PRUNE_MODULUS
+}
+
+fn nth_secret(secret: u64, n: usize) -> u64 {
+    (0..n).fold(secret, |s, _| next_secret(s))
+}
+
+fn sum_of_secrets(secrets: &[u64], n: usize) -> u64 {
+    secrets.iter().map(|&secret| nth_secret(secret, n)).sum()
+}
+
+/// Find the four-change sequence that yields the most bananas across all buyers.
+/// Each buyer sells at the first occurrence of the sequence, so totals are collected
+/// in a dense array indexed by the encoded sequence, with `last_seen` recording which
+/// buyer last touched an entry instead of clearing a set per buyer.
+fn most_bananas(secrets: &[u64], n: usize) -> u64 {
+    let mut totals = vec![0u64; SEQUENCE_COUNT];
+    let mut last_seen = vec![usize::MAX; SEQUENCE_COUNT];
+
+    for (buyer, &initial) in secrets.iter().enumerate() {
+        let mut secret = initial;
+        let mut price = (secret % 10) as usize;
+        let mut key = 0;
+
+        for step in 0..n {
+            secret = next_secret(secret);
+            let next_price = (secret % 10) as usize;
+            let delta = next_price + 9 - price;
+
+            // Shift the newest change in, dropping the oldest one
+            key = (key * DELTA_RANGE + delta) % SEQUENCE_COUNT;
+            price = next_price;
+
+            if step >= 3 && last_seen[key] != buyer {
+                last_seen[key] = buyer;
+                totals[key] += price as u64;
+            }
+        }
+    }
+
+    totals.into_iter().max().unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_secret_sequence() {
+        let mut secret = 123;
+        let expected = [
+            15887950, 16495136, 527345, 704524, 1553684, 12683156, 11100544, 12249484, 7753432,
+            5908254,
+        ];
+        for &expected_secret in &expected {
+            secret = next_secret(secret);
+            assert_eq!(secret, expected_secret);
+        }
+    }
+
+    #[test]
+    fn test_huge_secret() {
+        // Only the pruned bits of the secret affect the next one
+        let secrets = parse_input("18446744073709551615").unwrap();
+        assert_eq!(
+            next_secret(secrets[0]),
+            next_secret(secrets[0] % PRUNE_MODULUS)
+        );
+    }
+
+    #[test]
+    fn test_part_one() {
+        let secrets = parse_input("1\n10\n100\n2024").unwrap();
+        let sum = sum_of_secrets(&secrets, 2000);
+
+        let expected_sum = 37327623;
+        assert_eq!(sum, expected_sum);
+    }
+
+    #[test]
+    fn test_part_two() {
+        let secrets = parse_input("1\n2\n3\n2024").unwrap();
+        let bananas = most_bananas(&secrets, 2000);
+
+        let expected_bananas = 23;
+        assert_eq!(bananas, expected_bananas);
+    }
+}
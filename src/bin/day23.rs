@@ -0,0 +1,105 @@
+use aoc2024::fetch_or_load_input;
+use aoc2024::graph::Graph;
+use std::error::Error;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let day = 23;
+    let input = fetch_or_load_input(day)?;
+
+    let graph = parse_input(&input)?;
+
+    let count = count_triangles_with_t(&graph);
+    println!("Triangles with a t-computer part one: {}", count);
+
+    let password = lan_party_password(&graph);
+    println!("LAN party password part two: {}", password);
+
+    Ok(())
+}
+
+fn parse_input(input: &str) -> Result<Graph<String>, Box<dyn Error>> {
+    let mut graph = Graph::new();
+    for line in input.lines() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let (a, b) = line
+            .split_once('-')
+            .ok_or_else(|| format!("Invalid link: '{}'", line))?;
+        graph.add_edge(a.to_string(), b.to_string());
+    }
+    Ok(graph)
+}
+
+/// Count the sets of three inter-connected computers where at least one name starts with 't'.
+fn count_triangles_with_t(graph: &Graph<String>) -> usize {
+    graph
+        .triangles()
+        .iter()
+        .filter(|triangle| triangle.iter().any(|name| name.starts_with('t')))
+        .count()
+}
+
+/// The password is the names in the largest clique, sorted and joined by commas.
+fn lan_party_password(graph: &Graph<String>) -> String {
+    graph.max_clique().join(",")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_DATA: &str = "\
+kh-tc
+qp-kh
+de-cg
+ka-co
+yn-aq
+qp-ub
+cg-tb
+vc-aq
+tb-ka
+wh-tc
+yn-cg
+kh-ub
+ta-co
+de-co
+tc-td
+tb-wq
+wh-td
+ta-ka
+td-qp
+aq-cg
+wq-ub
+ub-vc
+de-ta
+wq-aq
+wq-vc
+wh-yn
+ka-de
+kh-ta
+co-tc
+wh-qp
+tb-vc
+td-yn";
+
+    #[test]
+    fn test_part_one() {
+        let graph = parse_input(TEST_DATA).unwrap();
+        let count = count_triangles_with_t(&graph);
+
+        let expected_count = 7;
+        assert_eq!(count, expected_count);
+    }
+
+    #[test]
+    fn test_part_two() {
+        let graph = parse_input(TEST_DATA).unwrap();
+        let password = lan_party_password(&graph);
+
+        let expected_password = "co,de,ka,ta";
+        assert_eq!(password, expected_password);
+    }
+}
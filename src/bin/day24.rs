@@ -0,0 +1,539 @@
+use aoc2024::fetch_or_load_input;
+use std::collections::{BTreeSet, HashMap};
+use std::env;
+use std::error::Error;
+use std::fmt::Write;
+use std::fs;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let day = 24;
+    let input = fetch_or_load_input(day)?;
+
+    let circuit = parse_input(&input)?;
+
+    let z_value = simulate(&circuit)?;
+    println!("Z output part one: {}", z_value);
+
+    let violations = check_adder(&circuit);
+    for violation in &violations {
+        println!("{}", violation);
+    }
+    println!(
+        "Swapped wires part two: {}",
+        swapped_wires(&circuit, &violations, 4)?
+    );
+
+    // Optionally export the circuit for inspection: `day24 --dot circuit.dot`
+    let args: Vec<String> = env::args().collect();
+    if let Some(idx) = args.iter().position(|arg| arg == "--dot") {
+        let path = args.get(idx + 1).ok_or("Missing path after --dot")?;
+        fs::write(path, to_dot(&circuit, &violations))?;
+        println!("Wrote circuit graph to {}", path);
+    }
+
+    Ok(())
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum GateKind {
+    And,
+    Or,
+    Xor,
+}
+
+impl GateKind {
+    fn from_str(s: &str) -> Option<GateKind> {
+        match s {
+            "AND" => Some(GateKind::And),
+            "OR" => Some(GateKind::Or),
+            "XOR" => Some(GateKind::Xor),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            GateKind::And => "AND",
+            GateKind::Or => "OR",
+            GateKind::Xor => "XOR",
+        }
+    }
+
+    fn apply(self, a: bool, b: bool) -> bool {
+        match self {
+            GateKind::And => a && b,
+            GateKind::Or => a || b,
+            GateKind::Xor => a ^ b,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Gate {
+    inputs: (String, String),
+    kind: GateKind,
+    output: String,
+}
+
+impl Gate {
+    fn has_input(&self, wire: &str) -> bool {
+        self.inputs.0 == wire || self.inputs.1 == wire
+    }
+
+    /// True if both inputs are primary x/y wires.
+    fn is_primary(&self) -> bool {
+        is_primary_wire(&self.inputs.0) && is_primary_wire(&self.inputs.1)
+    }
+
+    /// True if the inputs are the least significant bits x00 and y00.
+    fn is_first_bit(&self) -> bool {
+        let (a, b) = (&self.inputs.0, &self.inputs.1);
+        (a == "x00" && b == "y00") || (a == "y00" && b == "x00")
+    }
+}
+
+fn is_primary_wire(wire: &str) -> bool {
+    wire.starts_with('x') || wire.starts_with('y')
+}
+
+struct Circuit {
+    initial_values: HashMap<String, bool>,
+    gates: Vec<Gate>,
+}
+
+fn parse_input(input: &str) -> Result<Circuit, Box<dyn Error>> {
+    let mut initial_values = HashMap::new();
+    let mut gates = Vec::new();
+
+    for line in input.lines() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        if let Some((wire, value)) = line.split_once(':') {
+            // Initial value: "x00: 1"
+            let value = match value.trim() {
+                "0" => false,
+                "1" => true,
+                _ => return Err(format!("Invalid wire value: '{}'", line).into()),
+            };
+            initial_values.insert(wire.trim().to_string(), value);
+        } else {
+            // Gate: "x00 AND y00 -> z00"
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            if parts.len() != 5 || parts[3] != "->" {
+                return Err(format!("Invalid gate: '{}'", line).into());
+            }
+            let kind = GateKind::from_str(parts[1])
+                .ok_or_else(|| format!("Unknown gate type: '{}'", parts[1]))?;
+            gates.push(Gate {
+                inputs: (parts[0].to_string(), parts[2].to_string()),
+                kind,
+                output: parts[4].to_string(),
+            });
+        }
+    }
+
+    Ok(Circuit {
+        initial_values,
+        gates,
+    })
+}
+
+/// Propagate the initial values through the gates and read the z-wires as a binary number,
+/// with z00 as the least significant bit.
+fn simulate(circuit: &Circuit) -> Result<u64, Box<dyn Error>> {
+    evaluate(&circuit.gates, circuit.initial_values.clone())
+}
+
+/// Run the circuit as an adder on `x` and `y` instead of its initial values.
+fn add(gates: &[Gate], input_bits: u32, x: u64, y: u64) -> Result<u64, Box<dyn Error>> {
+    let mut values = HashMap::new();
+    for bit in 0..input_bits {
+        values.insert(format!("x{:02}", bit), (x >> bit) & 1 == 1);
+        values.insert(format!("y{:02}", bit), (y >> bit) & 1 == 1);
+    }
+    evaluate(gates, values)
+}
+
+fn evaluate(gates: &[Gate], mut values: HashMap<String, bool>) -> Result<u64, Box<dyn Error>> {
+    let mut pending: Vec<&Gate> = gates.iter().collect();
+
+    while !pending.is_empty() {
+        let before = pending.len();
+        pending.retain(
+            |gate| match (values.get(&gate.inputs.0), values.get(&gate.inputs.1)) {
+                (Some(&a), Some(&b)) => {
+                    values.insert(gate.output.clone(), gate.kind.apply(a, b));
+                    false
+                }
+                _ => true,
+            },
+        );
+        if pending.len() == before {
+            return Err("Circuit contains a cycle or an undriven wire".into());
+        }
+    }
+
+    let mut z_wires: Vec<(&String, &bool)> = values
+        .iter()
+        .filter(|(wire, _)| wire.starts_with('z'))
+        .collect();
+    z_wires.sort();
+    if z_wires.len() > u64::BITS as usize {
+        return Err(format!("Circuit has {} output bits, at most 64 fit", z_wires.len()).into());
+    }
+
+    let z_value = z_wires
+        .iter()
+        .rev()
+        .fold(0, |acc, (_, &bit)| (acc << 1) | bit as u64);
+    Ok(z_value)
+}
+
+/// A gate whose output breaks one of the ripple-carry adder invariants.
+#[derive(Debug)]
+struct Violation {
+    output: String,
+    gate: String,
+    reason: &'static str,
+}
+
+impl std::fmt::Display for Violation {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}: {} ({})", self.output, self.reason, self.gate)
+    }
+}
+
+/// Check the structure of the circuit against a ripple-carry adder.
+/// For each bit i > 0 the adder is
+///   xi XOR yi -> si,  xi AND yi -> ai,  si XOR c -> zi,  si AND c -> bi,  ai OR bi -> c'
+/// and bit 0 is a half adder whose AND output is the first carry.
+fn check_adder(circuit: &Circuit) -> Vec<Violation> {
+    let last_z = circuit
+        .gates
+        .iter()
+        .map(|gate| &gate.output)
+        .filter(|wire| wire.starts_with('z'))
+        .max()
+        .cloned()
+        .unwrap_or_default();
+
+    let feeds = |wire: &str, kind: GateKind| {
+        circuit
+            .gates
+            .iter()
+            .any(|gate| gate.kind == kind && gate.has_input(wire))
+    };
+
+    let mut violations = Vec::new();
+    for gate in &circuit.gates {
+        let output = gate.output.as_str();
+        let reason = if output == last_z {
+            // The final carry comes straight out of an OR gate
+            (gate.kind != GateKind::Or).then_some("final carry must come from OR")
+        } else if output.starts_with('z') {
+            (gate.kind != GateKind::Xor).then_some("sum bit must come from XOR")
+        } else {
+            match gate.kind {
+                GateKind::Xor if !gate.is_primary() => {
+                    Some("XOR of carry and partial sum must drive a z-wire")
+                }
+                GateKind::Xor if !feeds(output, GateKind::Xor) => {
+                    Some("partial sum must feed the XOR that produces the sum bit")
+                }
+                GateKind::And if !gate.is_first_bit() && !feeds(output, GateKind::Or) => {
+                    Some("AND output must feed the carry OR")
+                }
+                GateKind::Or if !feeds(output, GateKind::And) || !feeds(output, GateKind::Xor) => {
+                    Some("carry must feed both the next AND and XOR")
+                }
+                _ => None,
+            }
+        };
+
+        if let Some(reason) = reason {
+            violations.push(Violation {
+                output: output.to_string(),
+                gate: format!(
+                    "{} {} {} -> {}",
+                    gate.inputs.0,
+                    gate.kind.name(),
+                    gate.inputs.1,
+                    gate.output
+                ),
+                reason,
+            });
+        }
+    }
+
+    violations
+}
+
+/// The distinct outputs involved in violations, sorted and joined by commas.
+/// Errors unless there are exactly two wires per swap, and they pair up into `swaps`
+/// swaps after which the circuit passes the structure checks and adds correctly.
+fn swapped_wires(
+    circuit: &Circuit,
+    violations: &[Violation],
+    swaps: usize,
+) -> Result<String, Box<dyn Error>> {
+    let wires: BTreeSet<&str> = violations.iter().map(|v| v.output.as_str()).collect();
+    let wires: Vec<&str> = wires.into_iter().collect();
+    if wires.len() != 2 * swaps {
+        return Err(format!(
+            "Expected {} swapped wires, but {} outputs break the adder: {}",
+            2 * swaps,
+            wires.len(),
+            wires.join(",")
+        )
+        .into());
+    }
+
+    let mut pairs = Vec::new();
+    if !find_fixing_swaps(circuit, &wires, &mut pairs) {
+        return Err(format!("No swaps among {} fix the adder", wires.join(",")).into());
+    }
+    Ok(wires.join(","))
+}
+
+/// Try every way to pair up the remaining wires, returning true once the swaps in
+/// `pairs` fix the adder.
+fn find_fixing_swaps<'a>(
+    circuit: &Circuit,
+    wires: &[&'a str],
+    pairs: &mut Vec<(&'a str, &'a str)>,
+) -> bool {
+    let Some((&first, rest)) = wires.split_first() else {
+        return fixes_adder(circuit, pairs);
+    };
+    for (i, &second) in rest.iter().enumerate() {
+        let mut remaining = rest.to_vec();
+        remaining.remove(i);
+        pairs.push((first, second));
+        if find_fixing_swaps(circuit, &remaining, pairs) {
+            return true;
+        }
+        pairs.pop();
+    }
+    false
+}
+
+/// Whether swapping the outputs of each pair of gates gives a circuit that both
+/// passes `check_adder` and adds numbers correctly.
+fn fixes_adder(circuit: &Circuit, pairs: &[(&str, &str)]) -> bool {
+    let mut gates = circuit.gates.clone();
+    for gate in &mut gates {
+        for &(a, b) in pairs {
+            if gate.output == a {
+                gate.output = b.to_string();
+            } else if gate.output == b {
+                gate.output = a.to_string();
+            }
+        }
+    }
+
+    let swapped = Circuit {
+        initial_values: circuit.initial_values.clone(),
+        gates,
+    };
+    if !check_adder(&swapped).is_empty() {
+        return false;
+    }
+
+    // Single bits, carries into every position, and a carry through all of them
+    let input_bits = circuit
+        .initial_values
+        .keys()
+        .filter(|wire| wire.starts_with('x'))
+        .count() as u32;
+    if input_bits == 0 || input_bits >= u64::BITS {
+        return false;
+    }
+    let all_ones = (1u64 << input_bits) - 1;
+    let mut cases = vec![(0, 0), (all_ones, 1), (all_ones, all_ones)];
+    for bit in 0..input_bits {
+        cases.push((1 << bit, 0));
+        cases.push((0, 1 << bit));
+        cases.push((1 << bit, 1 << bit));
+    }
+    cases
+        .into_iter()
+        .all(|(x, y)| add(&swapped.gates, input_bits, x, y).ok() == Some(x + y))
+}
+
+/// Export the circuit as a Graphviz DOT graph. Wires are ellipses, gates are boxes,
+/// and wires driven by a violating gate are drawn in red.
+fn to_dot(circuit: &Circuit, violations: &[Violation]) -> String {
+    let flagged: BTreeSet<&str> = violations.iter().map(|v| v.output.as_str()).collect();
+
+    let mut dot = String::new();
+    dot.push_str("digraph circuit {\n    rankdir=LR;\n");
+
+    let mut wires: BTreeSet<&str> = circuit.initial_values.keys().map(|w| w.as_str()).collect();
+    wires.extend(circuit.gates.iter().map(|gate| gate.output.as_str()));
+    for wire in wires {
+        if flagged.contains(wire) {
+            let _ = writeln!(dot, "    {} [color=red, fontcolor=red];", wire);
+        } else {
+            let _ = writeln!(dot, "    {};", wire);
+        }
+    }
+
+    for (i, gate) in circuit.gates.iter().enumerate() {
+        let _ = writeln!(
+            dot,
+            "    g{} [shape=box, label=\"{}\"];",
+            i,
+            gate.kind.name()
+        );
+        let _ = writeln!(dot, "    {} -> g{};", gate.inputs.0, i);
+        let _ = writeln!(dot, "    {} -> g{};", gate.inputs.1, i);
+        let _ = writeln!(dot, "    g{} -> {};", i, gate.output);
+    }
+
+    dot.push_str("}\n");
+    dot
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_DATA: &str = "\
+x00: 1
+x01: 1
+x02: 1
+y00: 0
+y01: 1
+y02: 0
+
+x00 AND y00 -> z00
+x01 XOR y01 -> z01
+x02 OR y02 -> z02";
+
+    // A correct two-bit ripple-carry adder
+    const ADDER: &str = "\
+x00: 1
+x01: 1
+y00: 1
+y01: 0
+
+x00 XOR y00 -> z00
+x00 AND y00 -> c00
+x01 XOR y01 -> s01
+x01 AND y01 -> a01
+s01 XOR c00 -> z01
+s01 AND c00 -> b01
+a01 OR b01 -> z02";
+
+    #[test]
+    fn test_part_one() {
+        let circuit = parse_input(TEST_DATA).unwrap();
+        let z_value = simulate(&circuit).unwrap();
+
+        let expected_z_value = 4;
+        assert_eq!(z_value, expected_z_value);
+    }
+
+    #[test]
+    fn test_correct_adder_has_no_violations() {
+        let circuit = parse_input(ADDER).unwrap();
+        assert_eq!(simulate(&circuit).unwrap(), 3 + 1);
+        assert!(check_adder(&circuit).is_empty());
+    }
+
+    #[test]
+    fn test_swapped_outputs_are_found() {
+        let swapped = ADDER
+            .replace("x01 AND y01 -> a01", "x01 AND y01 -> z01")
+            .replace("s01 XOR c00 -> z01", "s01 XOR c00 -> a01");
+        let circuit = parse_input(&swapped).unwrap();
+        let violations = check_adder(&circuit);
+
+        assert_eq!(swapped_wires(&circuit, &violations, 1).unwrap(), "a01,z01");
+        assert!(swapped_wires(&circuit, &violations, 2).is_err());
+    }
+
+    /// An n-bit ripple-carry adder using the same wire names as `ADDER`.
+    fn ripple_adder(bits: usize) -> String {
+        let mut lines = Vec::new();
+        for bit in 0..bits {
+            lines.push(format!("x{:02}: 0", bit));
+            lines.push(format!("y{:02}: 0", bit));
+        }
+        lines.push(String::new());
+        lines.push("x00 XOR y00 -> z00".to_string());
+        lines.push("x00 AND y00 -> c00".to_string());
+        for bit in 1..bits {
+            let carry_out = if bit + 1 == bits {
+                format!("z{:02}", bits)
+            } else {
+                format!("c{:02}", bit)
+            };
+            lines.push(format!("x{0:02} XOR y{0:02} -> s{0:02}", bit));
+            lines.push(format!("x{0:02} AND y{0:02} -> a{0:02}", bit));
+            lines.push(format!("s{0:02} XOR c{1:02} -> z{0:02}", bit, bit - 1));
+            lines.push(format!("s{0:02} AND c{1:02} -> b{0:02}", bit, bit - 1));
+            lines.push(format!("a{0:02} OR b{0:02} -> {1}", bit, carry_out));
+        }
+        lines.join("\n")
+    }
+
+    #[test]
+    fn test_four_swaps_are_found() {
+        let mut swapped = ripple_adder(8);
+        for (a, b) in [
+            ("z02", "c02"),
+            ("s04", "a04"),
+            ("z05", "b05"),
+            ("z07", "a07"),
+        ] {
+            swapped = swapped
+                .replace(&format!("-> {}", a), "-> tmp")
+                .replace(&format!("-> {}", b), &format!("-> {}", a))
+                .replace("-> tmp", &format!("-> {}", b));
+        }
+        let circuit = parse_input(&swapped).unwrap();
+        let violations = check_adder(&circuit);
+
+        let expected_wires = "a04,a07,b05,c02,s04,z02,z05,z07";
+        assert_eq!(
+            swapped_wires(&circuit, &violations, 4).unwrap(),
+            expected_wires
+        );
+    }
+
+    #[test]
+    fn test_broken_gate_is_not_a_swap() {
+        // A wrong gate type breaks the adder, but no swap can fix it
+        let broken = ADDER.replace("x01 AND y01 -> a01", "x01 OR y01 -> a01");
+        let circuit = parse_input(&broken).unwrap();
+        let violations = check_adder(&circuit);
+
+        assert!(!violations.is_empty());
+        assert!(swapped_wires(&circuit, &violations, 1).is_err());
+    }
+
+    #[test]
+    fn test_correct_adder_adds() {
+        let circuit = parse_input(ADDER).unwrap();
+        for (x, y) in [(0, 0), (1, 2), (3, 3), (2, 1)] {
+            assert_eq!(add(&circuit.gates, 2, x, y).unwrap(), x + y);
+        }
+
+        let circuit = parse_input(&ripple_adder(8)).unwrap();
+        assert!(check_adder(&circuit).is_empty());
+        assert_eq!(add(&circuit.gates, 8, 200, 100).unwrap(), 300);
+    }
+
+    #[test]
+    fn test_too_many_output_bits() {
+        let gates: String = (0..65)
+            .map(|bit| format!("x00 AND y00 -> z{:02}\n", bit))
+            .collect();
+        let circuit = parse_input(&format!("x00: 1\ny00: 1\n\n{}", gates)).unwrap();
+        assert!(simulate(&circuit).is_err());
+    }
+}
@@ -0,0 +1,140 @@
+use aoc2024::fetch_or_load_input;
+use std::error::Error;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let day = 25;
+    let input = fetch_or_load_input(day)?;
+
+    let (locks, keys) = parse_input(&input)?;
+
+    let count = count_fitting_pairs(&locks, &keys);
+    println!("Fitting lock/key pairs part one: {}", count);
+
+    Ok(())
+}
+
+const COLUMNS: usize = 5;
+const ROWS: usize = 7;
+
+/// Pin heights per column, not counting the solid base row.
+type Heights = [u8; COLUMNS];
+
+/// Parse the schematics into lock and key height profiles.
+/// Schematics are separated by a blank line. Locks have a filled top row,
+/// keys have a filled bottom row.
+fn parse_input(input: &str) -> Result<(Vec<Heights>, Vec<Heights>), Box<dyn Error>> {
+    let mut locks = Vec::new();
+    let mut keys = Vec::new();
+
+    let lines: Vec<&str> = input.lines().map(|line| line.trim()).collect();
+    for schematic in lines.split(|line| line.is_empty()) {
+        if schematic.is_empty() {
+            continue;
+        }
+        if schematic.len() != ROWS || schematic.iter().any(|row| row.len() != COLUMNS) {
+            return Err(format!("Invalid schematic:\n{}", schematic.join("\n")).into());
+        }
+
+        let mut heights = [0u8; COLUMNS];
+        for row in &schematic[1..ROWS - 1] {
+            for (c, ch) in row.chars().enumerate() {
+                if ch == '#' {
+                    heights[c] += 1;
+                }
+            }
+        }
+
+        if schematic[0] == "#####" {
+            locks.push(heights);
+        } else if schematic[ROWS - 1] == "#####" {
+            keys.push(heights);
+        } else {
+            return Err(format!(
+                "Schematic is neither lock nor key:\n{}",
+                schematic.join("\n")
+            )
+            .into());
+        }
+    }
+
+    Ok((locks, keys))
+}
+
+/// A key fits a lock if no column overlaps, i.e. the heights sum to at most the free space.
+fn count_fitting_pairs(locks: &[Heights], keys: &[Heights]) -> usize {
+    let space = (ROWS - 2) as u8;
+    locks
+        .iter()
+        .map(|lock| {
+            keys.iter()
+                .filter(|key| lock.iter().zip(key.iter()).all(|(l, k)| l + k <= space))
+                .count()
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_DATA: &str = "\
+#####
+.####
+.####
+.####
+.#.#.
+.#...
+.....
+
+#####
+##.##
+.#.##
+...##
+...#.
+...#.
+.....
+
+.....
+#....
+#....
+#...#
+#.#.#
+#.###
+#####
+
+.....
+.....
+#.#..
+###..
+###.#
+###.#
+#####
+
+.....
+.....
+.....
+#....
+#.#..
+#.#.#
+#####";
+
+    #[test]
+    fn test_heights() {
+        let (locks, keys) = parse_input(TEST_DATA).unwrap();
+
+        assert_eq!(locks, vec![[0, 5, 3, 4, 3], [1, 2, 0, 5, 3]]);
+        assert_eq!(
+            keys,
+            vec![[5, 0, 2, 1, 3], [4, 3, 4, 0, 2], [3, 0, 2, 0, 1]]
+        );
+    }
+
+    #[test]
+    fn test_part_one() {
+        let (locks, keys) = parse_input(TEST_DATA).unwrap();
+        let count = count_fitting_pairs(&locks, &keys);
+
+        let expected_count = 3;
+        assert_eq!(count, expected_count);
+    }
+}
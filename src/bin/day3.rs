@@ -1,117 +1,644 @@
 use aoc2024::fetch_or_load_input;
-use regex::Regex;
+use std::env;
 use std::error::Error;
+use std::fmt;
+use std::io::Read;
 
 fn main() -> Result<(), Box<dyn Error>> {
     let day = 3;
     let input = fetch_or_load_input(day)?;
 
-    let tokens = tokenize_input(&input)?;
+    // `--extended` adds add/sub and nested calls, `--width 32|64|128` sets the
+    // accumulator width and `--trace` lists every instruction
+    let args: Vec<String> = env::args().collect();
+    let width = match args.iter().position(|arg| arg == "--width") {
+        Some(idx) => match args.get(idx + 1).map(|w| w.as_str()) {
+            Some("32") => Width::I32,
+            Some("64") => Width::I64,
+            Some("128") => Width::I128,
+            _ => return Err("Expected 32, 64 or 128 after --width".into()),
+        },
+        None => Width::I64,
+    };
+    let instructions = if args.iter().any(|arg| arg == "--extended") {
+        InstructionSet::extended()
+    } else {
+        InstructionSet::standard()
+    };
 
-    let total_part_one = process_tokens(&tokens);
-    println!("Total part one: {}", total_part_one);
+    let tokens = tokenize_input(&input, &instructions)?;
 
-    let total_part_two = process_tokens_with_dos_and_donts(&tokens);
-    println!("Total with do() and don't(): {}", total_part_two);
+    let part_one = Evaluator::new(&instructions, width, false).evaluate(&tokens)?;
+    println!("Total part one: {}", part_one.total);
+
+    let part_two = Evaluator::new(&instructions, width, true).evaluate(&tokens)?;
+    println!("Total with do() and don't(): {}", part_two.total);
+
+    if args.iter().any(|arg| arg == "--trace") {
+        for entry in &part_two.trace {
+            println!("{}", entry);
+        }
+    }
 
     Ok(())
 }
 
-#[derive(Debug)]
-enum Token {
-    Do,
-    Dont,
-    Mul(i32, i32),
+/// A parsed instruction call, e.g. `mul(2,4)` or `mul(add(1,2),4)`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Call {
+    name: String,
+    args: Vec<Arg>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Arg {
+    Number(i128),
+    Call(Call),
 }
 
+impl fmt::Display for Call {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}(", self.name)?;
+        for (i, arg) in self.args.iter().enumerate() {
+            if i > 0 {
+                write!(f, ",")?;
+            }
+            match arg {
+                Arg::Number(value) => write!(f, "{}", value)?,
+                Arg::Call(call) => write!(f, "{}", call)?,
+            }
+        }
+        write!(f, ")")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 struct PositionedToken {
-    token: Token,
+    call: Call,
+    /// Byte offset of the first character of the token
     position: usize,
+    /// 1-based line of the token
+    line: usize,
+    /// 1-based byte column of the token within its line
+    column: usize,
 }
 
-fn tokenize_input(input: &str) -> Result<Vec<PositionedToken>, Box<dyn Error>> {
-    // Regular expressions for the tokens
-    let re_mul = Regex::new(r"mul\(\s*(-?\d+)\s*,\s*(-?\d+)\s*\)")?;
-    let re_do = Regex::new(r"do\(\)")?;
-    let re_dont = Regex::new(r"don't\(\)")?;
+/// Computes the value of an instruction from its evaluated arguments.
+/// Returns None if the result cannot be represented.
+type ValueFn = fn(&[i128]) -> Option<i128>;
 
-    // Collect all matches with their positions
-    let mut tokens = Vec::new();
+#[derive(Clone, Copy)]
+enum Behavior {
+    /// Produces a value; top-level calls add it to the accumulator
+    Value(ValueFn),
+    /// Ends a conditional block, so following values are executed again
+    Enable,
+    /// Starts a conditional block whose values are skipped
+    Disable,
+}
+
+/// Which arguments an instruction accepts. Calls with arguments outside the
+/// rules are treated as corrupted memory and not matched at all.
+#[derive(Debug, Clone, Copy)]
+struct ArgumentRules {
+    /// Longest allowed number, in digits
+    max_digits: Option<usize>,
+    allow_negative: bool,
+    /// Whether arguments may themselves be value instructions
+    allow_nested: bool,
+}
+
+impl Default for ArgumentRules {
+    fn default() -> Self {
+        ArgumentRules {
+            max_digits: None,
+            allow_negative: true,
+            allow_nested: false,
+        }
+    }
+}
+
+struct Instruction {
+    name: String,
+    arity: usize,
+    behavior: Behavior,
+    rules: ArgumentRules,
+}
+
+impl Instruction {
+    fn value(name: &str, arity: usize, apply: ValueFn) -> Self {
+        Instruction {
+            name: name.to_string(),
+            arity,
+            behavior: Behavior::Value(apply),
+            rules: ArgumentRules::default(),
+        }
+    }
 
-    for mat in re_mul.find_iter(input) {
-        let cap = re_mul.captures(&input[mat.start()..mat.end()]).unwrap();
-        let x: i32 = cap[1].parse()?;
-        let y: i32 = cap[2].parse()?;
-        tokens.push(PositionedToken {
-            token: Token::Mul(x, y),
-            position: mat.start(),
-        });
+    fn enable(name: &str) -> Self {
+        Instruction {
+            name: name.to_string(),
+            arity: 0,
+            behavior: Behavior::Enable,
+            rules: ArgumentRules::default(),
+        }
     }
 
-    for mat in re_do.find_iter(input) {
-        tokens.push(PositionedToken {
-            token: Token::Do,
-            position: mat.start(),
-        });
+    fn disable(name: &str) -> Self {
+        Instruction {
+            name: name.to_string(),
+            arity: 0,
+            behavior: Behavior::Disable,
+            rules: ArgumentRules::default(),
+        }
     }
 
-    for mat in re_dont.find_iter(input) {
-        tokens.push(PositionedToken {
-            token: Token::Dont,
-            position: mat.start(),
-        });
+    fn with_rules(mut self, rules: ArgumentRules) -> Self {
+        self.rules = rules;
+        self
+    }
+}
+
+/// The registry of instructions the lexer recognizes and the evaluator executes.
+#[derive(Default)]
+struct InstructionSet {
+    instructions: Vec<Instruction>,
+}
+
+impl InstructionSet {
+    fn with(mut self, instruction: Instruction) -> Self {
+        self.instructions.push(instruction);
+        self
     }
 
-    // Sort tokens by their starting position
-    tokens.sort_by_key(|t| t.position);
+    /// The puzzle instructions: `mul(X,Y)`, `do()` and `don't()`.
+    fn standard() -> Self {
+        InstructionSet::default()
+            .with(Instruction::value("mul", 2, |args| {
+                args[0].checked_mul(args[1])
+            }))
+            .with(Instruction::enable("do"))
+            .with(Instruction::disable("don't"))
+    }
+
+    /// The puzzle instructions plus `add` and `sub`, all accepting nested calls.
+    fn extended() -> Self {
+        let nested = ArgumentRules {
+            allow_nested: true,
+            ..ArgumentRules::default()
+        };
+        InstructionSet::default()
+            .with(
+                Instruction::value("mul", 2, |args| args[0].checked_mul(args[1]))
+                    .with_rules(nested),
+            )
+            .with(
+                Instruction::value("add", 2, |args| args[0].checked_add(args[1]))
+                    .with_rules(nested),
+            )
+            .with(
+                Instruction::value("sub", 2, |args| args[0].checked_sub(args[1]))
+                    .with_rules(nested),
+            )
+            .with(Instruction::enable("do"))
+            .with(Instruction::disable("don't"))
+    }
+
+    fn get(&self, name: &str) -> Option<&Instruction> {
+        self.instructions.iter().find(|i| i.name == name)
+    }
+}
+
+fn tokenize_input(
+    input: &str,
+    instructions: &InstructionSet,
+) -> Result<Vec<PositionedToken>, Box<dyn Error>> {
+    tokenize_reader(input.as_bytes(), instructions)
+}
+
+/// Tokenize everything `reader` produces, one fixed-size chunk at a time.
+fn tokenize_reader<R: Read>(
+    mut reader: R,
+    instructions: &InstructionSet,
+) -> Result<Vec<PositionedToken>, Box<dyn Error>> {
+    let mut lexer = Lexer::new(instructions);
+    let mut tokens = Vec::new();
+    let mut chunk = vec![0u8; 64 * 1024];
+
+    loop {
+        let read = reader.read(&mut chunk)?;
+        if read == 0 {
+            break;
+        }
+        lexer.feed(&chunk[..read], &mut tokens)?;
+    }
+    lexer.finish(&mut tokens)?;
 
     Ok(tokens)
 }
 
-fn process_tokens(tokens: &[PositionedToken]) -> i32 {
-    let mut total = 0;
+/// Outcome of trying to match a token, or a part of one, in a byte slice.
+enum Scan<T> {
+    Match(T),
+    NoMatch,
+    /// The slice ends while it is still a valid prefix of a token
+    Incomplete,
+}
+
+/// Single-pass incremental lexer. Input can be fed in arbitrary chunks; a token that
+/// spans a chunk boundary is kept pending until the next chunk completes or rejects it.
+struct Lexer<'a> {
+    instructions: &'a InstructionSet,
+    /// Bytes that can start an instruction name
+    first_bytes: [bool; 256],
+    pending: Vec<u8>,
+    /// Byte offset, line and column of `pending[0]`
+    offset: usize,
+    line: usize,
+    column: usize,
+}
 
-    for token in tokens {
-        if let Token::Mul(x, y) = token.token {
-            total += x * y;
+impl<'a> Lexer<'a> {
+    fn new(instructions: &'a InstructionSet) -> Self {
+        let mut first_bytes = [false; 256];
+        for instruction in &instructions.instructions {
+            if let Some(&b) = instruction.name.as_bytes().first() {
+                first_bytes[b as usize] = true;
+            }
+        }
+
+        Lexer {
+            instructions,
+            first_bytes,
+            pending: Vec::new(),
+            offset: 0,
+            line: 1,
+            column: 1,
         }
     }
 
-    total
+    /// Lex a chunk, appending all tokens that are complete to `tokens`.
+    fn feed(
+        &mut self,
+        chunk: &[u8],
+        tokens: &mut Vec<PositionedToken>,
+    ) -> Result<(), Box<dyn Error>> {
+        self.pending.extend_from_slice(chunk);
+        self.scan(tokens, false)
+    }
+
+    /// Lex whatever is still pending at the end of the input.
+    fn finish(mut self, tokens: &mut Vec<PositionedToken>) -> Result<(), Box<dyn Error>> {
+        self.scan(tokens, true)
+    }
+
+    fn scan(
+        &mut self,
+        tokens: &mut Vec<PositionedToken>,
+        at_end: bool,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut i = 0;
+        while i < self.pending.len() {
+            let scan = if self.first_bytes[self.pending[i] as usize] {
+                scan_call(&self.pending, i, self.instructions, false)?
+            } else {
+                Scan::NoMatch
+            };
+
+            let next = match scan {
+                Scan::Match((call, next)) => {
+                    tokens.push(PositionedToken {
+                        call,
+                        position: self.offset + i,
+                        line: self.line,
+                        column: self.column,
+                    });
+                    next
+                }
+                Scan::Incomplete if !at_end => break,
+                Scan::Incomplete | Scan::NoMatch => i + 1,
+            };
+
+            for &b in &self.pending[i..next] {
+                if b == b'\n' {
+                    self.line += 1;
+                    self.column = 1;
+                } else {
+                    self.column += 1;
+                }
+            }
+            i = next;
+        }
+
+        self.pending.drain(..i);
+        self.offset += i;
+        Ok(())
+    }
 }
 
-fn process_tokens_with_dos_and_donts(tokens: &[PositionedToken]) -> i32 {
-    let mut enabled = true; // Initial state is enabled
-    let mut total = 0;
+// Unwrap a matched part, or return early if the token cannot (yet) be matched
+macro_rules! part {
+    ($scan:expr) => {
+        match $scan {
+            Scan::Match(value) => value,
+            Scan::NoMatch => return Ok(Scan::NoMatch),
+            Scan::Incomplete => return Ok(Scan::Incomplete),
+        }
+    };
+}
+
+/// Try to match any registered instruction at `pos`, returning the call and the
+/// position after it. Nested calls may only be value instructions.
+fn scan_call(
+    bytes: &[u8],
+    pos: usize,
+    instructions: &InstructionSet,
+    nested: bool,
+) -> Result<Scan<(Call, usize)>, Box<dyn Error>> {
+    let mut incomplete = false;
+    for instruction in &instructions.instructions {
+        if nested && !matches!(instruction.behavior, Behavior::Value(_)) {
+            continue;
+        }
+        match scan_instruction(bytes, pos, instruction, instructions)? {
+            Scan::Match(found) => return Ok(Scan::Match(found)),
+            Scan::Incomplete => incomplete = true,
+            Scan::NoMatch => {}
+        }
+    }
+
+    Ok(if incomplete {
+        Scan::Incomplete
+    } else {
+        Scan::NoMatch
+    })
+}
 
-    for token in tokens {
-        match token.token {
-            Token::Do => {
-                enabled = true;
+/// Match `name(arg,...)` for one instruction. Whitespace is allowed around arguments.
+fn scan_instruction(
+    bytes: &[u8],
+    pos: usize,
+    instruction: &Instruction,
+    instructions: &InstructionSet,
+) -> Result<Scan<(Call, usize)>, Box<dyn Error>> {
+    let pos = part!(match_literal(bytes, pos, instruction.name.as_bytes()));
+    let mut pos = part!(match_literal(bytes, pos, b"("));
+
+    let mut args = Vec::with_capacity(instruction.arity);
+    if instruction.arity > 0 {
+        loop {
+            let start = skip_whitespace(bytes, pos);
+            if start == bytes.len() {
+                return Ok(Scan::Incomplete);
             }
-            Token::Dont => {
-                enabled = false;
+
+            let is_number = bytes[start] == b'-' || bytes[start].is_ascii_digit();
+            let (arg, next) = if !is_number && instruction.rules.allow_nested {
+                let (call, next) = part!(scan_call(bytes, start, instructions, true)?);
+                (Arg::Call(call), next)
+            } else {
+                let (value, next) = part!(match_number(bytes, start, &instruction.rules));
+                (Arg::Number(value), next)
+            };
+            args.push(arg);
+
+            pos = skip_whitespace(bytes, next);
+            if args.len() == instruction.arity {
+                break;
             }
-            Token::Mul(x, y) => {
-                if enabled {
-                    total += x * y;
+            pos = part!(match_literal(bytes, pos, b","));
+        }
+    }
+    let pos = part!(match_literal(bytes, pos, b")"));
+
+    let call = Call {
+        name: instruction.name.clone(),
+        args,
+    };
+    Ok(Scan::Match((call, pos)))
+}
+
+/// Match `literal` at `pos`, returning the position after it.
+fn match_literal(bytes: &[u8], pos: usize, literal: &[u8]) -> Scan<usize> {
+    let available = &bytes[pos.min(bytes.len())..];
+    if available.starts_with(literal) {
+        Scan::Match(pos + literal.len())
+    } else if literal.starts_with(available) {
+        Scan::Incomplete
+    } else {
+        Scan::NoMatch
+    }
+}
+
+fn skip_whitespace(bytes: &[u8], mut pos: usize) -> usize {
+    while pos < bytes.len() && bytes[pos].is_ascii_whitespace() {
+        pos += 1;
+    }
+    pos
+}
+
+/// Match an optional minus sign and at least one digit at `pos`,
+/// returning the value and the position after it. A number too large for
+/// an i128 is corrupted memory like any other, so it does not match.
+fn match_number(bytes: &[u8], pos: usize, rules: &ArgumentRules) -> Scan<(i128, usize)> {
+    let mut end = pos;
+    if end < bytes.len() && bytes[end] == b'-' {
+        if !rules.allow_negative {
+            return Scan::NoMatch;
+        }
+        end += 1;
+    }
+    let digits_start = end;
+    while end < bytes.len() && bytes[end].is_ascii_digit() {
+        end += 1;
+    }
+
+    if rules.max_digits.is_some_and(|max| end - digits_start > max) {
+        return Scan::NoMatch;
+    }
+    if end == bytes.len() {
+        // The number may continue in the next chunk
+        return Scan::Incomplete;
+    }
+    if end == digits_start {
+        return Scan::NoMatch;
+    }
+
+    let digits = std::str::from_utf8(&bytes[pos..end]).expect("sign and digits are ASCII");
+    match digits.parse() {
+        Ok(value) => Scan::Match((value, end)),
+        Err(_) => Scan::NoMatch,
+    }
+}
+
+/// Width of the accumulator. Every argument, intermediate value and running
+/// total must fit, otherwise evaluation fails with an overflow error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Width {
+    I32,
+    I64,
+    I128,
+}
+
+impl Width {
+    fn fits(self, value: i128) -> bool {
+        match self {
+            Width::I32 => i32::try_from(value).is_ok(),
+            Width::I64 => i64::try_from(value).is_ok(),
+            Width::I128 => true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Outcome {
+    Executed(i128),
+    /// A value instruction inside a disabled block
+    Skipped,
+    Enabled,
+    Disabled,
+    /// A conditional instruction while conditionals are not evaluated
+    Ignored,
+}
+
+struct TraceEntry {
+    call: Call,
+    line: usize,
+    column: usize,
+    outcome: Outcome,
+}
+
+impl fmt::Display for TraceEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{} {} ", self.line, self.column, self.call)?;
+        match self.outcome {
+            Outcome::Executed(value) => write!(f, "executed = {}", value),
+            Outcome::Skipped => write!(f, "skipped"),
+            Outcome::Enabled => write!(f, "enabled"),
+            Outcome::Disabled => write!(f, "disabled"),
+            Outcome::Ignored => write!(f, "ignored"),
+        }
+    }
+}
+
+struct Evaluation {
+    total: i128,
+    trace: Vec<TraceEntry>,
+}
+
+struct Evaluator<'a> {
+    instructions: &'a InstructionSet,
+    width: Width,
+    /// Whether enable/disable instructions are honoured
+    conditionals: bool,
+}
+
+impl<'a> Evaluator<'a> {
+    fn new(instructions: &'a InstructionSet, width: Width, conditionals: bool) -> Self {
+        Evaluator {
+            instructions,
+            width,
+            conditionals,
+        }
+    }
+
+    fn evaluate(&self, tokens: &[PositionedToken]) -> Result<Evaluation, Box<dyn Error>> {
+        let mut enabled = true; // Initial state is enabled
+        let mut total: i128 = 0;
+        let mut trace = Vec::with_capacity(tokens.len());
+
+        for token in tokens {
+            let overflow = || {
+                format!(
+                    "Overflow evaluating {} at line {}, column {} with a {:?} accumulator",
+                    token.call, token.line, token.column, self.width
+                )
+            };
+
+            let outcome = match self.behavior(&token.call)? {
+                Behavior::Enable if self.conditionals => {
+                    enabled = true;
+                    Outcome::Enabled
                 }
-            }
+                Behavior::Disable if self.conditionals => {
+                    enabled = false;
+                    Outcome::Disabled
+                }
+                Behavior::Enable | Behavior::Disable => Outcome::Ignored,
+                Behavior::Value(_) if !enabled => Outcome::Skipped,
+                Behavior::Value(_) => {
+                    let value = self.value(&token.call)?.ok_or_else(overflow)?;
+                    total = total
+                        .checked_add(value)
+                        .filter(|&t| self.width.fits(t))
+                        .ok_or_else(overflow)?;
+                    Outcome::Executed(value)
+                }
+            };
+
+            trace.push(TraceEntry {
+                call: token.call.clone(),
+                line: token.line,
+                column: token.column,
+                outcome,
+            });
         }
+
+        Ok(Evaluation { total, trace })
     }
 
-    total
+    fn behavior(&self, call: &Call) -> Result<Behavior, Box<dyn Error>> {
+        self.instructions
+            .get(&call.name)
+            .map(|instruction| instruction.behavior)
+            .ok_or_else(|| format!("Unknown instruction: '{}'", call.name).into())
+    }
+
+    /// Evaluate a value instruction. Returns Ok(None) if any part overflows the width.
+    fn value(&self, call: &Call) -> Result<Option<i128>, Box<dyn Error>> {
+        let Behavior::Value(apply) = self.behavior(call)? else {
+            return Err(format!("'{}' does not produce a value", call.name).into());
+        };
+
+        let mut args = Vec::with_capacity(call.args.len());
+        for arg in &call.args {
+            let value = match arg {
+                Arg::Number(value) => Some(*value),
+                Arg::Call(nested) => self.value(nested)?,
+            };
+            match value.filter(|&v| self.width.fits(v)) {
+                Some(value) => args.push(value),
+                None => return Ok(None),
+            }
+        }
+
+        Ok(apply(&args).filter(|&v| self.width.fits(v)))
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn mul(x: i128, y: i128) -> Call {
+        Call {
+            name: "mul".to_string(),
+            args: vec![Arg::Number(x), Arg::Number(y)],
+        }
+    }
+
+    fn total(input: &str, instructions: &InstructionSet, conditionals: bool) -> i128 {
+        let tokens = tokenize_input(input, instructions).unwrap();
+        Evaluator::new(instructions, Width::I64, conditionals)
+            .evaluate(&tokens)
+            .unwrap()
+            .total
+    }
+
     #[test]
     fn test_input_part_one() {
         let input = "xmul(2,4)%&mul[3,7]!@^do_not_mul(5,5)+mul(32,64]then(mul(11,8)mul(8,5))";
-        let tokens = tokenize_input(input).unwrap();
-        let total = process_tokens(&tokens);
+        let total = total(input, &InstructionSet::standard(), false);
         let expected_total = 161;
         assert_eq!(total, expected_total);
     }
@@ -119,9 +646,120 @@ mod tests {
     #[test]
     fn test_input_with_dos_and_donts() {
         let input = "xmul(2,4)&mul[3,7]!^don't()_mul(5,5)+mul(32,64](mul(11,8)undo()?mul(8,5))";
-        let tokens = tokenize_input(input).unwrap();
-        let total = process_tokens_with_dos_and_donts(&tokens);
+        let total = total(input, &InstructionSet::standard(), true);
         let expected_total = 48;
         assert_eq!(total, expected_total);
     }
+
+    #[test]
+    fn test_chunked_input_matches_whole_input() {
+        let input =
+            "xmul(2,4)&mul[3,7]!^don't()_mul( 5 , -5 )+mul(32,64](mul(11,8)undo()?mul(8,5))";
+        let instructions = InstructionSet::extended();
+        let expected = tokenize_input(input, &instructions).unwrap();
+        assert_eq!(expected.len(), 6);
+
+        for chunk_size in 1..=8 {
+            let mut lexer = Lexer::new(&instructions);
+            let mut tokens = Vec::new();
+            for chunk in input.as_bytes().chunks(chunk_size) {
+                lexer.feed(chunk, &mut tokens).unwrap();
+            }
+            lexer.finish(&mut tokens).unwrap();
+            assert_eq!(tokens, expected, "chunk size {}", chunk_size);
+        }
+    }
+
+    #[test]
+    fn test_token_positions() {
+        let input = "mul(1,2)\nxdo()\n  mul(3,4";
+        let tokens = tokenize_input(input, &InstructionSet::standard()).unwrap();
+
+        let expected_tokens = [
+            PositionedToken {
+                call: mul(1, 2),
+                position: 0,
+                line: 1,
+                column: 1,
+            },
+            PositionedToken {
+                call: Call {
+                    name: "do".to_string(),
+                    args: Vec::new(),
+                },
+                position: 10,
+                line: 2,
+                column: 2,
+            },
+        ];
+        assert_eq!(tokens, expected_tokens);
+    }
+
+    #[test]
+    fn test_nested_calls() {
+        let input = "add(1,2)mul(mul(2,3), 4)don't()sub(10,3)do()sub(10,add(1,2))";
+
+        // The standard set has no nesting, so only the inner mul(2,3) matches
+        assert_eq!(total(input, &InstructionSet::standard(), true), 6);
+
+        let instructions = InstructionSet::extended();
+        assert_eq!(total(input, &instructions, false), 3 + 24 + 7 + 7);
+
+        let tokens = tokenize_input(input, &instructions).unwrap();
+        let evaluation = Evaluator::new(&instructions, Width::I64, true)
+            .evaluate(&tokens)
+            .unwrap();
+        let outcomes: Vec<Outcome> = evaluation.trace.iter().map(|e| e.outcome).collect();
+        let expected_outcomes = [
+            Outcome::Executed(3),
+            Outcome::Executed(24),
+            Outcome::Disabled,
+            Outcome::Skipped,
+            Outcome::Enabled,
+            Outcome::Executed(7),
+        ];
+        assert_eq!(outcomes, expected_outcomes);
+        assert_eq!(evaluation.total, 34);
+    }
+
+    #[test]
+    fn test_accumulator_width() {
+        let instructions = InstructionSet::standard();
+        let tokens = tokenize_input("mul(100000,100000)", &instructions).unwrap();
+
+        let narrow = Evaluator::new(&instructions, Width::I32, false).evaluate(&tokens);
+        assert!(narrow.is_err());
+
+        let wide = Evaluator::new(&instructions, Width::I64, false)
+            .evaluate(&tokens)
+            .unwrap();
+        assert_eq!(wide.total, 10_000_000_000);
+    }
+
+    #[test]
+    fn test_argument_rules() {
+        let rules = ArgumentRules {
+            max_digits: Some(3),
+            allow_negative: false,
+            ..ArgumentRules::default()
+        };
+        let instructions = InstructionSet::default().with(
+            Instruction::value("mul", 2, |args| args[0].checked_mul(args[1])).with_rules(rules),
+        );
+
+        let tokens = tokenize_input("mul(1234,2)mul(-1,2)mul(123,2)", &instructions).unwrap();
+        let calls: Vec<Call> = tokens.into_iter().map(|t| t.call).collect();
+        assert_eq!(calls, vec![mul(123, 2)]);
+    }
+
+    #[test]
+    fn test_overflowing_number_is_skipped() {
+        let instructions = InstructionSet::standard();
+        let huge = "9".repeat(40);
+        let input = format!("mul({},2)mul(3,4)", huge);
+        let tokens = tokenize_input(&input, &instructions).unwrap();
+
+        let calls: Vec<Call> = tokens.into_iter().map(|t| t.call).collect();
+        assert_eq!(calls, vec![mul(3, 4)]);
+    }
 }
@@ -1,4 +1,6 @@
 use aoc2024::fetch_or_load_input;
+use std::collections::HashSet;
+use std::env;
 use std::error::Error;
 
 fn main() -> Result<(), Box<dyn Error>> {
@@ -16,6 +18,42 @@ fn main() -> Result<(), Box<dyn Error>> {
         total_crossing_mas_occurences
     );
 
+    // `day4 --search WORD,WORD [--orthogonal]` looks for other words in the grid
+    let args: Vec<String> = env::args().collect();
+    if let Some(idx) = args.iter().position(|arg| arg == "--search") {
+        let words: Vec<&str> = args
+            .get(idx + 1)
+            .ok_or("Missing words after --search")?
+            .split(',')
+            .collect();
+        let directions: &[(isize, isize)] = if args.iter().any(|arg| arg == "--orthogonal") {
+            &ORTHOGONAL_DIRECTIONS
+        } else {
+            &ALL_DIRECTIONS
+        };
+        for found in find_words(&grid, &words, directions) {
+            println!(
+                "{} at {:?} direction {:?}",
+                found.word, found.start, found.direction
+            );
+        }
+    }
+
+    // `day4 --highlight` prints the grid with only the matched letters shown
+    if args.iter().any(|arg| arg == "--highlight") {
+        let word_cells: Vec<(usize, usize)> = find_words(&grid, &["XMAS"], &ALL_DIRECTIONS)
+            .iter()
+            .flat_map(|m| m.cells())
+            .collect();
+        println!("{}\n", render_highlighted(&grid, &word_cells));
+
+        let shape_cells: Vec<(usize, usize)> = find_shape(&grid, &Template::parse(CROSSING_MAS))
+            .into_iter()
+            .flat_map(|m| m.cells)
+            .collect();
+        println!("{}", render_highlighted(&grid, &shape_cells));
+    }
+
     Ok(())
 }
 
@@ -27,76 +65,238 @@ fn parse_grid(input: &str) -> Vec<Vec<char>> {
         .collect()
 }
 
-fn count_xmas_occurrences(grid: &[Vec<char>]) -> usize {
-    let word = "XMAS";
-    let word_len = word.len();
-    let directions = [
-        (-1, -1), // Up-Left
-        (-1, 0),  // Up
-        (-1, 1),  // Up-Right
-        (0, -1),  // Left
-        (0, 1),   // Right
-        (1, -1),  // Down-Left
-        (1, 0),   // Down
-        (1, 1),   // Down-Right
-    ];
-
-    let rows = grid.len();
-    let cols = grid[0].len();
-    let mut count = 0;
-
-    for i in 0..rows {
-        for j in 0..cols {
-            for &(dx, dy) in &directions {
-                let mut k = 0;
-                let mut x = i as isize;
-                let mut y = j as isize;
-
-                while k < word_len {
-                    if x < 0 || x >= rows as isize || y < 0 || y >= cols as isize {
-                        break;
+/// All eight directions as (row, col) steps.
+const ALL_DIRECTIONS: [(isize, isize); 8] = [
+    (-1, -1), // Up-Left
+    (-1, 0),  // Up
+    (-1, 1),  // Up-Right
+    (0, -1),  // Left
+    (0, 1),   // Right
+    (1, -1),  // Down-Left
+    (1, 0),   // Down
+    (1, 1),   // Down-Right
+];
+
+/// Left-to-right, right-to-left, top-to-bottom and bottom-to-top.
+const ORTHOGONAL_DIRECTIONS: [(isize, isize); 4] = [(-1, 0), (0, -1), (0, 1), (1, 0)];
+
+/// The X-shaped MAS of part two. '.' is a wildcard.
+const CROSSING_MAS: &str = "\
+M.S
+.A.
+M.S";
+
+/// A word found in the grid, starting at `start` and continuing in `direction`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct WordMatch {
+    word: String,
+    start: (usize, usize),
+    direction: (isize, isize),
+}
+
+impl WordMatch {
+    fn cells(&self) -> Vec<(usize, usize)> {
+        (0..self.word.chars().count() as isize)
+            .map(|k| {
+                (
+                    (self.start.0 as isize + k * self.direction.0) as usize,
+                    (self.start.1 as isize + k * self.direction.1) as usize,
+                )
+            })
+            .collect()
+    }
+}
+
+/// Find every occurrence of every word in the given directions. A word that
+/// reads the same along several directions over the same cells, like a single
+/// letter or a palindrome read backwards, is reported once, in the first of
+/// those directions.
+fn find_words(grid: &[Vec<char>], words: &[&str], directions: &[(isize, isize)]) -> Vec<WordMatch> {
+    let rows = grid.len() as isize;
+    let mut matches = Vec::new();
+
+    for word in words {
+        let letters: Vec<char> = word.chars().collect();
+        if letters.is_empty() {
+            continue;
+        }
+        let mut seen: HashSet<Vec<(usize, usize)>> = HashSet::new();
+
+        for (i, row) in grid.iter().enumerate() {
+            for j in 0..row.len() {
+                for &(dx, dy) in directions {
+                    let found = letters.iter().enumerate().all(|(k, &letter)| {
+                        let x = i as isize + k as isize * dx;
+                        let y = j as isize + k as isize * dy;
+                        x >= 0
+                            && x < rows
+                            && y >= 0
+                            && grid[x as usize].get(y as usize) == Some(&letter)
+                    });
+
+                    if !found {
+                        continue;
                     }
-                    if grid[x as usize][y as usize] != word.chars().nth(k).unwrap() {
-                        break;
+                    let found = WordMatch {
+                        word: word.to_string(),
+                        start: (i, j),
+                        direction: (dx, dy),
+                    };
+                    let mut cells = found.cells();
+                    cells.sort_unstable();
+                    if seen.insert(cells) {
+                        matches.push(found);
                     }
-                    x += dx;
-                    y += dy;
-                    k += 1;
                 }
+            }
+        }
+    }
+
+    matches
+}
+
+/// A rectangular pattern of letters where None matches any letter.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Template {
+    cells: Vec<Vec<Option<char>>>,
+}
+
+impl Template {
+    /// Parse a template from text, with '.' as the wildcard.
+    fn parse(text: &str) -> Self {
+        let cells = text
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| {
+                line.trim()
+                    .chars()
+                    .map(|c| if c == '.' { None } else { Some(c) })
+                    .collect()
+            })
+            .collect();
+        Template { cells }
+    }
+
+    fn rows(&self) -> usize {
+        self.cells.len()
+    }
+
+    fn cols(&self) -> usize {
+        self.cells.iter().map(|row| row.len()).max().unwrap_or(0)
+    }
+
+    fn get(&self, r: usize, c: usize) -> Option<char> {
+        self.cells[r].get(c).copied().flatten()
+    }
+
+    /// Rotate 90 degrees clockwise.
+    fn rotate(&self) -> Self {
+        let (rows, cols) = (self.rows(), self.cols());
+        let cells = (0..cols)
+            .map(|c| (0..rows).rev().map(|r| self.get(r, c)).collect())
+            .collect();
+        Template { cells }
+    }
+
+    /// Mirror left to right.
+    fn reflect(&self) -> Self {
+        let cols = self.cols();
+        let cells = (0..self.rows())
+            .map(|r| (0..cols).rev().map(|c| self.get(r, c)).collect())
+            .collect();
+        Template { cells }
+    }
 
-                if k == word_len {
-                    count += 1;
+    /// The distinct templates under all rotations and reflections.
+    fn variants(&self) -> Vec<Template> {
+        let mut variants: Vec<Template> = Vec::new();
+        for start in [self.clone(), self.reflect()] {
+            let mut current = start;
+            for _ in 0..4 {
+                if !variants.contains(&current) {
+                    variants.push(current.clone());
                 }
+                current = current.rotate();
             }
         }
+        variants
     }
+}
 
-    count
+/// A template placed in the grid with its top-left corner at `origin`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ShapeMatch {
+    origin: (usize, usize),
+    /// Grid cells covered by the non-wildcard letters
+    cells: Vec<(usize, usize)>,
 }
 
-fn count_crossing_mas_occurrences(grid: &[Vec<char>]) -> usize {
-    let rows = grid.len();
-    if rows == 0 {
-        return 0;
-    }
-    let cols = grid[0].len();
-    let mut count = 0;
-
-    // Iterate over the grid, avoiding the borders
-    for i in 1..rows - 1 {
-        for j in 1..cols - 1 {
-            if grid[i][j] == 'A' {
-                if ((grid[i - 1][j - 1] == 'M' && grid[i + 1][j + 1] == 'S')
-                    || (grid[i - 1][j - 1] == 'S' && grid[i + 1][j + 1] == 'M'))
-                    && ((grid[i - 1][j + 1] == 'M' && grid[i + 1][j - 1] == 'S')
-                        || (grid[i - 1][j + 1] == 'S' && grid[i + 1][j - 1] == 'M'))
-                {
-                    count += 1;
+/// Find every placement of the template under all its rotations and reflections.
+fn find_shape(grid: &[Vec<char>], template: &Template) -> Vec<ShapeMatch> {
+    let mut matches = Vec::new();
+
+    for variant in template.variants() {
+        let (height, width) = (variant.rows(), variant.cols());
+        if height > grid.len() {
+            continue;
+        }
+
+        for i in 0..=grid.len() - height {
+            let row_len = grid[i..i + height].iter().map(|row| row.len()).min();
+            let Some(available) = row_len.and_then(|len| len.checked_sub(width)) else {
+                continue;
+            };
+
+            for j in 0..=available {
+                let mut cells = Vec::new();
+                let mut found = true;
+                for r in 0..height {
+                    for c in 0..width {
+                        if let Some(letter) = variant.get(r, c) {
+                            if grid[i + r][j + c] != letter {
+                                found = false;
+                                break;
+                            }
+                            cells.push((i + r, j + c));
+                        }
+                    }
+                    if !found {
+                        break;
+                    }
+                }
+
+                if found {
+                    matches.push(ShapeMatch {
+                        origin: (i, j),
+                        cells,
+                    });
                 }
             }
         }
     }
-    count
+
+    matches
+}
+
+/// Render the grid with only the given cells shown and everything else as '.'.
+fn render_highlighted(grid: &[Vec<char>], cells: &[(usize, usize)]) -> String {
+    let mut shown: Vec<Vec<char>> = grid.iter().map(|row| vec!['.'; row.len()]).collect();
+    for &(r, c) in cells {
+        shown[r][c] = grid[r][c];
+    }
+    shown
+        .iter()
+        .map(|row| row.iter().collect::<String>())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn count_xmas_occurrences(grid: &[Vec<char>]) -> usize {
+    find_words(grid, &["XMAS"], &ALL_DIRECTIONS).len()
+}
+
+fn count_crossing_mas_occurrences(grid: &[Vec<char>]) -> usize {
+    find_shape(grid, &Template::parse(CROSSING_MAS)).len()
 }
 
 #[cfg(test)]
@@ -132,4 +332,74 @@ MXMXAXMASX";
         let expected_total = 9;
         assert_eq!(total_occurrences, expected_total);
     }
+
+    #[test]
+    fn test_find_words_positions() {
+        let grid = parse_grid("ABC\nDEF\nGHI");
+        let matches = find_words(&grid, &["FE", "AEI", "CFI"], &ORTHOGONAL_DIRECTIONS);
+
+        let expected_matches = [
+            WordMatch {
+                word: "FE".to_string(),
+                start: (1, 2),
+                direction: (0, -1),
+            },
+            WordMatch {
+                word: "CFI".to_string(),
+                start: (0, 2),
+                direction: (1, 0),
+            },
+        ];
+        assert_eq!(matches, expected_matches);
+        assert_eq!(matches[1].cells(), vec![(0, 2), (1, 2), (2, 2)]);
+    }
+
+    #[test]
+    fn test_find_words_reports_each_placement_once() {
+        let grid = parse_grid("ABA\nXEX\nXXX");
+
+        // One letter reads the same in every direction
+        let matches = find_words(&grid, &["E"], &ALL_DIRECTIONS);
+        let expected_matches = [WordMatch {
+            word: "E".to_string(),
+            start: (1, 1),
+            direction: (-1, -1),
+        }];
+        assert_eq!(matches, expected_matches);
+
+        // A palindrome reads the same forwards and backwards
+        let matches = find_words(&grid, &["ABA"], &ALL_DIRECTIONS);
+        let expected_matches = [WordMatch {
+            word: "ABA".to_string(),
+            start: (0, 0),
+            direction: (0, 1),
+        }];
+        assert_eq!(matches, expected_matches);
+    }
+
+    #[test]
+    fn test_template_variants() {
+        // The X-MAS shape only has four distinct orientations
+        assert_eq!(Template::parse(CROSSING_MAS).variants().len(), 4);
+
+        // An L-shape has all eight
+        let l_shape = Template::parse("X.\nX.\nXX");
+        assert_eq!(l_shape.variants().len(), 8);
+
+        let grid = parse_grid("....\n.XXX\n.X..");
+        let matches = find_shape(&grid, &l_shape);
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].origin, (1, 1));
+    }
+
+    #[test]
+    fn test_render_highlighted() {
+        let grid = parse_grid("XMASX\nSAMXM");
+        let cells: Vec<(usize, usize)> = find_words(&grid, &["XMAS"], &ALL_DIRECTIONS)
+            .iter()
+            .flat_map(|m| m.cells())
+            .collect();
+
+        assert_eq!(render_highlighted(&grid, &cells), "XMAS.\nSAMX.");
+    }
 }
@@ -1,5 +1,9 @@
 use aoc2024::fetch_or_load_input;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::env;
 use std::error::Error;
+use std::fmt::Write;
+use std::fs;
 
 fn main() -> Result<(), Box<dyn Error>> {
     let day = 5;
@@ -8,11 +12,24 @@ fn main() -> Result<(), Box<dyn Error>> {
     let (rules, print_jobs) = parse_input(&input)?;
     let validity = validate_print_jobs(&rules, &print_jobs);
 
+    // `day5 --analyze [--dot rules.dot]` reports on the rule set itself
+    let args: Vec<String> = env::args().collect();
+    if args.iter().any(|arg| arg == "--analyze") {
+        let analysis = analyze_rules(&rules, &print_jobs);
+        println!("{}", analysis);
+
+        if let Some(idx) = args.iter().position(|arg| arg == "--dot") {
+            let path = args.get(idx + 1).ok_or("Missing path after --dot")?;
+            fs::write(path, to_dot(&rules, &analysis.redundant))?;
+            println!("Wrote rule graph to {}", path);
+        }
+    }
+
     let valid_print_jobs: Vec<_> = print_jobs
         .clone()
         .into_iter()
         .zip(validity.iter())
-        .filter(|(_, &is_valid)| is_valid)
+        .filter(|(_, violation)| violation.is_none())
         .map(|(job, _)| job)
         .collect();
 
@@ -22,18 +39,28 @@ fn main() -> Result<(), Box<dyn Error>> {
     let invalid_print_jobs: Vec<_> = print_jobs
         .into_iter()
         .zip(validity.iter())
-        .filter(|(_, &is_valid)| !is_valid)
+        .filter(|(_, violation)| violation.is_some())
         .map(|(job, _)| job)
         .collect();
 
-    let corrected_jobs = correct_invalid_print_jobs(&invalid_print_jobs, &rules);
+    // `day5 --violations` lists the first rule each invalid job breaks
+    if args.iter().any(|arg| arg == "--violations") {
+        for (job, (x, y)) in invalid_print_jobs.iter().zip(validity.iter().flatten()) {
+            println!("{:?} violates {}|{}", job, x, y);
+        }
+    }
+
+    let corrected_jobs = correct_invalid_print_jobs(&invalid_print_jobs, &rules)?;
     let total_sum_corrected = sum_middle_pages(&corrected_jobs);
     println!("Total sum part two: {}", total_sum_corrected);
 
     Ok(())
 }
 
-fn parse_input(input: &str) -> Result<(Vec<(u32, u32)>, Vec<Vec<u32>>), Box<dyn Error>> {
+type Rules = Vec<(u32, u32)>;
+type PrintJobs = Vec<Vec<u32>>;
+
+fn parse_input(input: &str) -> Result<(Rules, PrintJobs), Box<dyn Error>> {
     let mut rules = Vec::new();
     let mut print_jobs = Vec::new();
     let mut is_rules_section = true;
@@ -67,27 +94,28 @@ fn parse_input(input: &str) -> Result<(Vec<(u32, u32)>, Vec<Vec<u32>>), Box<dyn
     Ok((rules, print_jobs))
 }
 
+/// The position of each page within a job.
+fn page_positions(job: &[u32]) -> HashMap<u32, usize> {
+    job.iter()
+        .enumerate()
+        .map(|(pos, &page)| (page, pos))
+        .collect()
+}
+
 /// Validates the print jobs based on the rules.
-fn validate_print_jobs(rules: &[(u32, u32)], print_jobs: &[Vec<u32>]) -> Vec<bool> {
+/// Each entry is None for a valid job, or the first rule the job violates.
+fn validate_print_jobs(rules: &[(u32, u32)], print_jobs: &[Vec<u32>]) -> Vec<Option<(u32, u32)>> {
     print_jobs
         .iter()
         .map(|job| {
-            let mut page_positions = std::collections::HashMap::new();
-            for (pos, &page) in job.iter().enumerate() {
-                page_positions.insert(page, pos);
-            }
-
-            for &(x, y) in rules {
-                if let (Some(&pos_x), Some(&pos_y)) =
-                    (page_positions.get(&x), page_positions.get(&y))
-                {
-                    if pos_y < pos_x {
-                        // Rule violated: y appears before x
-                        return false;
-                    }
-                }
-            }
-            true
+            let page_positions = page_positions(job);
+            rules.iter().copied().find(|(x, y)| {
+                // Rule violated: y appears before x
+                matches!(
+                    (page_positions.get(x), page_positions.get(y)),
+                    (Some(pos_x), Some(pos_y)) if pos_y < pos_x
+                )
+            })
         })
         .collect()
 }
@@ -103,41 +131,212 @@ fn sum_middle_pages(print_jobs: &[Vec<u32>]) -> u32 {
         .sum()
 }
 
-fn correct_print_job(mut job: Vec<u32>, rules: &[(u32, u32)]) -> Vec<u32> {
-    let mut changed = true;
+/// Order the pages of a job so that every rule between its pages holds.
+fn correct_print_job(job: &[u32], rules: &[(u32, u32)]) -> Result<Vec<u32>, Box<dyn Error>> {
+    if page_positions(job).len() != job.len() {
+        return Err(format!("Job {:?} contains a page more than once", job).into());
+    }
+
+    topological_order(job, rules).map_err(|cycle| {
+        let pages: Vec<String> = cycle.iter().map(|page| page.to_string()).collect();
+        format!("Rules form a cycle: {}", pages.join(" -> ")).into()
+    })
+}
 
-    while changed {
-        changed = false;
+/// Sort distinct pages with Kahn's algorithm on the rules restricted to them.
+/// Among pages that are ready, the one earliest in `pages` goes first, so the result
+/// is deterministic and pages that are not constrained keep their relative order.
+/// Returns a cycle among the pages if the rules cannot be satisfied.
+fn topological_order(pages: &[u32], rules: &[(u32, u32)]) -> Result<Vec<u32>, Vec<u32>> {
+    let positions = page_positions(pages);
 
-        let mut page_positions = std::collections::HashMap::new();
-        for (pos, &page) in job.iter().enumerate() {
-            page_positions.insert(page, pos);
+    // The graph is indexed by position in `pages`
+    let mut successors = vec![Vec::new(); pages.len()];
+    let mut predecessors = vec![Vec::new(); pages.len()];
+    let mut in_degree = vec![0usize; pages.len()];
+    for &(x, y) in rules {
+        if let (Some(&i), Some(&j)) = (positions.get(&x), positions.get(&y)) {
+            successors[i].push(j);
+            predecessors[j].push(i);
+            in_degree[j] += 1;
         }
+    }
 
-        for &(x, y) in rules {
-            if let (Some(&pos_x), Some(&pos_y)) = (page_positions.get(&x), page_positions.get(&y)) {
-                if pos_y < pos_x {
-                    // Move x to be right before y
-                    let x_page = job.remove(pos_x);
-                    let pos_y = page_positions[&y]; // Update pos_y after removing x
-                    job.insert(pos_y, x_page);
-                    changed = true;
-                    break; // Start over as positions have changed
-                }
+    let mut ready: BTreeSet<usize> = (0..pages.len()).filter(|&i| in_degree[i] == 0).collect();
+    let mut ordered = Vec::with_capacity(pages.len());
+    while let Some(i) = ready.pop_first() {
+        ordered.push(pages[i]);
+        for &j in &successors[i] {
+            in_degree[j] -= 1;
+            if in_degree[j] == 0 {
+                ready.insert(j);
             }
         }
     }
 
-    job
+    if ordered.len() < pages.len() {
+        let cycle = find_cycle(&predecessors, &in_degree);
+        return Err(cycle.iter().map(|&i| pages[i]).collect());
+    }
+
+    Ok(ordered)
+}
+
+/// Find a cycle among the nodes Kahn's algorithm could not place.
+/// Every such node still has an unplaced predecessor, so walking backwards
+/// must eventually revisit a node. The cycle is returned in rule order,
+/// starting and ending with the same node.
+fn find_cycle(predecessors: &[Vec<usize>], in_degree: &[usize]) -> Vec<usize> {
+    let Some(start) = (0..in_degree.len()).find(|&i| in_degree[i] > 0) else {
+        return Vec::new();
+    };
+
+    let mut walk = vec![start];
+    let mut seen = HashMap::from([(start, 0)]);
+    let mut current = start;
+    loop {
+        current = predecessors[current]
+            .iter()
+            .copied()
+            .find(|&p| in_degree[p] > 0)
+            .expect("unplaced node must have an unplaced predecessor");
+        if let Some(&idx) = seen.get(&current) {
+            let mut cycle = walk[idx..].to_vec();
+            cycle.push(current);
+            cycle.reverse();
+            return cycle;
+        }
+        seen.insert(current, walk.len());
+        walk.push(current);
+    }
 }
 
-fn correct_invalid_print_jobs(print_jobs: &[Vec<u32>], rules: &Vec<(u32, u32)>) -> Vec<Vec<u32>> {
+fn correct_invalid_print_jobs(
+    print_jobs: &[Vec<u32>],
+    rules: &[(u32, u32)],
+) -> Result<Vec<Vec<u32>>, Box<dyn Error>> {
     print_jobs
         .iter()
-        .map(|job| correct_print_job(job.clone(), rules))
+        .map(|job| correct_print_job(job, rules))
         .collect()
 }
 
+/// Successors of each page, with the index of the rule that gives the edge.
+fn rule_graph(rules: &[(u32, u32)]) -> BTreeMap<u32, Vec<(u32, usize)>> {
+    let mut graph: BTreeMap<u32, Vec<(u32, usize)>> = BTreeMap::new();
+    for (idx, &(x, y)) in rules.iter().enumerate() {
+        graph.entry(x).or_default().push((y, idx));
+        graph.entry(y).or_default();
+    }
+    graph
+}
+
+/// Pages reachable from `start` by one or more rules, ignoring the rule at index `skip`.
+fn reachable(
+    graph: &BTreeMap<u32, Vec<(u32, usize)>>,
+    start: u32,
+    skip: Option<usize>,
+) -> BTreeSet<u32> {
+    let mut seen = BTreeSet::new();
+    let mut stack = vec![start];
+    while let Some(page) = stack.pop() {
+        for &(next, idx) in &graph[&page] {
+            if Some(idx) != skip && seen.insert(next) {
+                stack.push(next);
+            }
+        }
+    }
+    seen
+}
+
+/// Every ordering implied by the rules, directly or through a chain of rules.
+fn transitive_closure(rules: &[(u32, u32)]) -> BTreeSet<(u32, u32)> {
+    let graph = rule_graph(rules);
+    graph
+        .keys()
+        .flat_map(|&x| reachable(&graph, x, None).into_iter().map(move |y| (x, y)))
+        .collect()
+}
+
+/// Rules that are implied by the remaining rules, including repeated rules.
+/// Each one can be removed on its own without changing the closure. When the rules
+/// contain cycles, removing several of them at once may not be safe.
+fn redundant_rules(rules: &[(u32, u32)]) -> Vec<(u32, u32)> {
+    let graph = rule_graph(rules);
+    rules
+        .iter()
+        .enumerate()
+        .filter(|&(idx, &(x, y))| reachable(&graph, x, Some(idx)).contains(&y))
+        .map(|(_, &rule)| rule)
+        .collect()
+}
+
+/// What can be said about a rule set as a whole.
+struct RuleAnalysis {
+    closure: BTreeSet<(u32, u32)>,
+    redundant: Vec<(u32, u32)>,
+    /// A cycle through the full rule set, if there is one
+    global_cycle: Option<Vec<u32>>,
+    /// Indices of the jobs whose own pages form a cycle
+    cyclic_jobs: Vec<usize>,
+}
+
+fn analyze_rules(rules: &[(u32, u32)], print_jobs: &[Vec<u32>]) -> RuleAnalysis {
+    let pages: Vec<u32> = rule_graph(rules).into_keys().collect();
+
+    RuleAnalysis {
+        closure: transitive_closure(rules),
+        redundant: redundant_rules(rules),
+        global_cycle: topological_order(&pages, rules).err(),
+        cyclic_jobs: print_jobs
+            .iter()
+            .enumerate()
+            .filter(|(_, job)| topological_order(job, rules).is_err())
+            .map(|(idx, _)| idx)
+            .collect(),
+    }
+}
+
+impl std::fmt::Display for RuleAnalysis {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(f, "Implied orderings: {}", self.closure.len())?;
+        writeln!(f, "Redundant rules: {}", self.redundant.len())?;
+        match &self.global_cycle {
+            None => writeln!(f, "Rules are globally acyclic")?,
+            Some(cycle) => {
+                let pages: Vec<String> = cycle.iter().map(|page| page.to_string()).collect();
+                writeln!(f, "Rules are cyclic: {}", pages.join(" -> "))?;
+            }
+        }
+        if self.cyclic_jobs.is_empty() {
+            write!(f, "Rules are acyclic within every job")
+        } else {
+            write!(f, "Jobs with cyclic rules: {:?}", self.cyclic_jobs)
+        }
+    }
+}
+
+/// Export the precedence graph as Graphviz DOT, with redundant rules dashed.
+fn to_dot(rules: &[(u32, u32)], redundant: &[(u32, u32)]) -> String {
+    let redundant: BTreeSet<&(u32, u32)> = redundant.iter().collect();
+
+    let mut dot = String::new();
+    dot.push_str("digraph rules {\n");
+    for rule in rules {
+        if redundant.contains(rule) {
+            let _ = writeln!(
+                dot,
+                "    {} -> {} [style=dashed, color=gray];",
+                rule.0, rule.1
+            );
+        } else {
+            let _ = writeln!(dot, "    {} -> {};", rule.0, rule.1);
+        }
+    }
+    dot.push_str("}\n");
+    dot
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -180,7 +379,7 @@ mod tests {
         let valid_print_jobs: Vec<_> = print_jobs
             .into_iter()
             .zip(validity.iter())
-            .filter(|(_, &is_valid)| is_valid)
+            .filter(|(_, violation)| violation.is_none())
             .map(|(job, _)| job)
             .collect();
 
@@ -198,14 +397,81 @@ mod tests {
         let invalid_print_jobs: Vec<_> = print_jobs
             .into_iter()
             .zip(validity.iter())
-            .filter(|(_, &is_valid)| !is_valid)
+            .filter(|(_, violation)| violation.is_some())
             .map(|(job, _)| job)
             .collect();
 
-        let corrected_jobs = correct_invalid_print_jobs(&invalid_print_jobs, &rules);
+        let corrected_jobs = correct_invalid_print_jobs(&invalid_print_jobs, &rules).unwrap();
         let total_sum = sum_middle_pages(&corrected_jobs);
 
         let expected_total = 123;
         assert_eq!(total_sum, expected_total);
     }
+
+    #[test]
+    fn test_violated_rules() {
+        let (rules, print_jobs) = parse_input(TEST_DATA).unwrap();
+        let validity = validate_print_jobs(&rules, &print_jobs);
+
+        let expected_validity = vec![
+            None,
+            None,
+            None,
+            Some((97, 75)),
+            Some((29, 13)),
+            Some((29, 13)),
+        ];
+        assert_eq!(validity, expected_validity);
+    }
+
+    #[test]
+    fn test_ordering_is_deterministic() {
+        // 5 and 7 are unconstrained, so they keep their original order
+        let rules = vec![(3, 1)];
+        let ordered = correct_print_job(&[5, 1, 7, 3], &rules).unwrap();
+
+        assert_eq!(ordered, vec![5, 7, 3, 1]);
+    }
+
+    #[test]
+    fn test_cycle_is_reported() {
+        let rules = vec![(1, 2), (2, 3), (3, 1), (4, 1)];
+        let error = correct_print_job(&[4, 3, 2, 1], &rules).unwrap_err();
+
+        assert_eq!(error.to_string(), "Rules form a cycle: 3 -> 1 -> 2 -> 3");
+
+        // The cycle does not matter for jobs that miss one of its pages
+        assert_eq!(
+            correct_print_job(&[3, 4, 2], &rules).unwrap(),
+            vec![4, 2, 3]
+        );
+    }
+
+    #[test]
+    fn test_rule_analysis() {
+        let (rules, print_jobs) = parse_input(TEST_DATA).unwrap();
+        let analysis = analyze_rules(&rules, &print_jobs);
+
+        // The example rules are a total order of 7 pages
+        let expected_closure_size = 7 * 6 / 2;
+        assert_eq!(analysis.closure.len(), expected_closure_size);
+        assert_eq!(analysis.redundant.len(), rules.len() - 6);
+        assert_eq!(analysis.global_cycle, None);
+        assert!(analysis.cyclic_jobs.is_empty());
+    }
+
+    #[test]
+    fn test_cyclic_only_globally() {
+        let rules = vec![(1, 2), (2, 3), (3, 1), (1, 3)];
+        let print_jobs = vec![vec![1, 2], vec![2, 3], vec![1, 2, 3]];
+        let analysis = analyze_rules(&rules, &print_jobs);
+
+        assert!(transitive_closure(&rules).contains(&(1, 1)));
+        assert_eq!(analysis.redundant, vec![(1, 3)]);
+        assert_eq!(analysis.global_cycle, Some(vec![1, 2, 3, 1]));
+        assert_eq!(analysis.cyclic_jobs, vec![2]);
+
+        let dot = to_dot(&rules, &analysis.redundant);
+        assert!(dot.contains("    1 -> 3 [style=dashed, color=gray];"));
+    }
 }
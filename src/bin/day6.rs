@@ -1,22 +1,82 @@
 use aoc2024::fetch_or_load_input;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use std::env;
 use std::error::Error;
+use std::fs;
+use std::thread;
+use std::time::Duration;
 
 fn main() -> Result<(), Box<dyn Error>> {
     let day = 6;
     let input = fetch_or_load_input(day)?;
 
-    let (grid, guard_pos, guard_dir) = parse_input(&input)?;
-    let (_final_grid, count, _has_loop) = simulate(grid.clone(), guard_pos, guard_dir);
+    let (grid, guards) = parse_input(&input)?;
+
+    // `day6 --turn left|right|reverse` picks what guards do at an obstacle
+    let args: Vec<String> = env::args().collect();
+    let policy = match args.iter().position(|arg| arg == "--turn") {
+        Some(idx) => {
+            TurnPolicy::from_name(args.get(idx + 1).ok_or("Missing policy after --turn")?)?
+        }
+        None => TurnPolicy::Right,
+    };
+
+    let count = count_visited(&grid, &guards, policy);
     println!("Total count part one: {}", count);
 
-    let loop_count = get_loop_count(&grid, guard_pos, guard_dir);
+    let loop_count = count_loop_obstructions(&grid, &guards, policy);
     println!("Total count of loops: {}", loop_count);
 
+    let wants_single_guard = args
+        .iter()
+        .any(|arg| arg == "--animate" || arg == "--export-loops");
+    if wants_single_guard && guards.len() != 1 {
+        return Err("--animate and --export-loops need a single guard".into());
+    }
+    let Guard {
+        position: guard_pos,
+        direction: guard_dir,
+    } = guards[0];
+
+    // `day6 --animate [delay_ms]` replays the patrol in the terminal
+    if let Some(idx) = args.iter().position(|arg| arg == "--animate") {
+        let delay_ms = match args.get(idx + 1) {
+            Some(value) if !value.starts_with("--") => value.parse()?,
+            _ => 50,
+        };
+        let patrol = trace_patrol(&grid, guard_pos, guard_dir, None, policy);
+        animate(&grid, &patrol, Duration::from_millis(delay_ms));
+        println!(
+            "Patrol of {} steps with {} turns",
+            patrol.steps.len() - 1,
+            patrol.turns()
+        );
+    }
+
+    // `day6 --export-loops loops.txt` writes every loop obstruction with its cycle
+    if let Some(idx) = args.iter().position(|arg| arg == "--export-loops") {
+        let path = args
+            .get(idx + 1)
+            .ok_or("Missing path after --export-loops")?;
+        let obstructions = find_loop_obstructions(&grid, guard_pos, guard_dir, policy);
+        let mut export = String::new();
+        for &obstruction in &obstructions {
+            let patrol = trace_patrol(&grid, guard_pos, guard_dir, Some(obstruction), policy);
+            export.push_str(&format!(
+                "Obstruction at {:?}, loop of {} steps\n{}\n\n",
+                obstruction,
+                patrol.cycle().len(),
+                render_loop(&grid, &patrol, obstruction)
+            ));
+        }
+        fs::write(path, export)?;
+        println!("Wrote {} loops to {}", obstructions.len(), path);
+    }
+
     Ok(())
 }
 
-#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
 enum Direction {
     Up,
     Right,
@@ -25,6 +85,16 @@ enum Direction {
 }
 
 impl Direction {
+    fn from_glyph(glyph: char) -> Option<Self> {
+        match glyph {
+            '^' => Some(Direction::Up),
+            '>' => Some(Direction::Right),
+            'v' => Some(Direction::Down),
+            '<' => Some(Direction::Left),
+            _ => None,
+        }
+    }
+
     fn turn_right(self) -> Self {
         match self {
             Direction::Up => Direction::Right,
@@ -34,6 +104,27 @@ impl Direction {
         }
     }
 
+    fn turn_left(self) -> Self {
+        self.turn_right().turn_right().turn_right()
+    }
+
+    fn reverse(self) -> Self {
+        self.turn_right().turn_right()
+    }
+
+    fn glyph(self) -> char {
+        match self {
+            Direction::Up => '^',
+            Direction::Right => '>',
+            Direction::Down => 'v',
+            Direction::Left => '<',
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+
     fn to_delta(self) -> (isize, isize) {
         match self {
             Direction::Up => (-1, 0),
@@ -44,137 +135,599 @@ impl Direction {
     }
 }
 
-fn parse_input(input: &str) -> Result<(Vec<Vec<char>>, (usize, usize), Direction), Box<dyn Error>> {
-    let lines: Vec<&str> = input.lines().collect();
+/// What a guard does when the way ahead is blocked.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum TurnPolicy {
+    Left,
+    Right,
+    Reverse,
+}
+
+impl TurnPolicy {
+    fn from_name(name: &str) -> Result<Self, Box<dyn Error>> {
+        match name {
+            "left" => Ok(TurnPolicy::Left),
+            "right" => Ok(TurnPolicy::Right),
+            "reverse" => Ok(TurnPolicy::Reverse),
+            _ => Err(format!("Unknown turn policy: '{}'", name).into()),
+        }
+    }
+
+    fn turn(self, direction: Direction) -> Direction {
+        match self {
+            TurnPolicy::Left => direction.turn_left(),
+            TurnPolicy::Right => direction.turn_right(),
+            TurnPolicy::Reverse => direction.reverse(),
+        }
+    }
+}
+
+type Grid = Vec<Vec<char>>;
+type Position = (usize, usize);
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+struct Guard {
+    position: Position,
+    direction: Direction,
+}
+
+/// Parse the map and every guard on it, facing the way its glyph points.
+/// The glyphs stay in the grid and count as free cells.
+fn parse_input(input: &str) -> Result<(Grid, Vec<Guard>), Box<dyn Error>> {
     let mut grid: Vec<Vec<char>> = Vec::new();
+    let mut guards = Vec::new();
 
-    let mut guard_pos = None;
-    for (r, line) in lines.iter().enumerate() {
+    for (r, line) in input.lines().enumerate() {
         let row: Vec<char> = line.chars().collect();
         for (c, &ch) in row.iter().enumerate() {
-            if ch == '^' {
-                guard_pos = Some((r, c));
+            if let Some(direction) = Direction::from_glyph(ch) {
+                guards.push(Guard {
+                    position: (r, c),
+                    direction,
+                });
             }
         }
         grid.push(row);
     }
 
-    let guard_pos = guard_pos.ok_or("No guard found in input")?;
-    // According to the problem, '^' means the guard is facing upwards.
-    let guard_dir = Direction::Up;
+    if guards.is_empty() {
+        return Err("No guard found in input".into());
+    }
+
+    Ok((grid, guards))
+}
+
+/// The guard's state after one step of the patrol. A turn keeps the position
+/// and changes the direction.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+struct PatrolStep {
+    position: Position,
+    direction: Direction,
+    turned: bool,
+}
+
+/// The full route of the guard, starting with its initial state.
+struct Patrol {
+    steps: Vec<PatrolStep>,
+    /// Index of the first step of the loop, if the guard never leaves the map
+    loop_start: Option<usize>,
+}
+
+impl Patrol {
+    fn visited(&self) -> HashSet<Position> {
+        self.steps.iter().map(|step| step.position).collect()
+    }
+
+    /// The steps that repeat forever, or nothing if the guard leaves the map.
+    fn cycle(&self) -> &[PatrolStep] {
+        match self.loop_start {
+            Some(start) => &self.steps[start..],
+            None => &[],
+        }
+    }
 
-    Ok((grid, guard_pos, guard_dir))
+    fn turns(&self) -> usize {
+        self.steps.iter().filter(|step| step.turned).count()
+    }
+}
+
+/// Record the guard's patrol, optionally with an extra obstruction, until it either
+/// leaves the map or returns to a state it has been in before.
+fn trace_patrol(
+    grid: &[Vec<char>],
+    start_pos: Position,
+    start_dir: Direction,
+    obstruction: Option<Position>,
+    policy: TurnPolicy,
+) -> Patrol {
+    let rows = grid.len() as isize;
+    let cols = grid[0].len() as isize;
+
+    let mut steps = vec![PatrolStep {
+        position: start_pos,
+        direction: start_dir,
+        turned: false,
+    }];
+    let mut seen = HashMap::from([((start_pos, start_dir), 0)]);
+
+    loop {
+        let current = steps[steps.len() - 1];
+        let (dr, dc) = current.direction.to_delta();
+        let next_r = current.position.0 as isize + dr;
+        let next_c = current.position.1 as isize + dc;
+
+        if next_r < 0 || next_r >= rows || next_c < 0 || next_c >= cols {
+            // Next step would leave the map
+            return Patrol {
+                steps,
+                loop_start: None,
+            };
+        }
+
+        let next_pos = (next_r as usize, next_c as usize);
+        let step = if grid[next_pos.0][next_pos.1] == '#' || Some(next_pos) == obstruction {
+            PatrolStep {
+                position: current.position,
+                direction: policy.turn(current.direction),
+                turned: true,
+            }
+        } else {
+            PatrolStep {
+                position: next_pos,
+                direction: current.direction,
+                turned: false,
+            }
+        };
+
+        if let Some(&start) = seen.get(&(step.position, step.direction)) {
+            return Patrol {
+                steps,
+                loop_start: Some(start),
+            };
+        }
+        seen.insert((step.position, step.direction), steps.len());
+        steps.push(step);
+    }
 }
 
 fn simulate(
     mut grid: Vec<Vec<char>>,
     start_pos: (usize, usize),
     start_dir: Direction,
+    policy: TurnPolicy,
 ) -> (Vec<Vec<char>>, usize, bool) {
-    let rows = grid.len();
-    let cols = grid[0].len();
+    let patrol = trace_patrol(&grid, start_pos, start_dir, None, policy);
+    let visited = patrol.visited();
 
-    let mut direction = start_dir;
-    let mut position = start_pos;
+    // Mark visited positions with 'X' on the grid
+    for &(r, c) in &visited {
+        grid[r][c] = 'X';
+    }
 
-    // Keep track of visited positions in a set
-    let mut visited = HashSet::new();
-    visited.insert(position);
+    (grid, visited.len(), patrol.loop_start.is_some())
+}
 
-    // dir_grid: tracks the direction of the guard when it visited a cell last time
-    // None means never visited, Some(Direction) means visited with that direction
-    let mut dir_grid = vec![vec![None; cols]; rows];
+/// For every cell and direction, where the guard stops when walking straight:
+/// the last free cell before an obstacle, or None if it walks off the map.
+struct JumpTable {
+    cols: usize,
+    stops: Vec<[Option<Position>; 4]>,
+}
 
-    let mut has_loop = false;
+impl JumpTable {
+    fn new(grid: &[Vec<char>]) -> Self {
+        let rows = grid.len();
+        let cols = grid[0].len();
+        let mut stops = vec![[None; 4]; rows * cols];
+
+        // Sweep each line against the walking direction, remembering the cell
+        // just past the most recent obstacle
+        for c in 0..cols {
+            let mut stop = None;
+            for r in 0..rows {
+                if grid[r][c] == '#' {
+                    stop = Some((r + 1, c));
+                } else {
+                    stops[r * cols + c][Direction::Up.index()] = stop;
+                }
+            }
+            stop = None;
+            for r in (0..rows).rev() {
+                if grid[r][c] == '#' {
+                    stop = r.checked_sub(1).map(|r| (r, c));
+                } else {
+                    stops[r * cols + c][Direction::Down.index()] = stop;
+                }
+            }
+        }
+        for (r, row) in grid.iter().enumerate() {
+            let mut stop = None;
+            for (c, &cell) in row.iter().enumerate() {
+                if cell == '#' {
+                    stop = Some((r, c + 1));
+                } else {
+                    stops[r * cols + c][Direction::Left.index()] = stop;
+                }
+            }
+            stop = None;
+            for (c, &cell) in row.iter().enumerate().rev() {
+                if cell == '#' {
+                    stop = c.checked_sub(1).map(|c| (r, c));
+                } else {
+                    stops[r * cols + c][Direction::Right.index()] = stop;
+                }
+            }
+        }
 
-    // Mark the starting position direction
-    dir_grid[position.0][position.1] = Some(direction);
+        JumpTable { cols, stops }
+    }
 
-    // Function to check if a position is not an obstacle
-    let is_free = |r: isize, c: isize| {
-        let cell = grid[r as usize][c as usize];
-        if cell == '#' {
-            None // Obstacle
-        } else {
-            Some((r as usize, c as usize))
+    /// Where the guard stops walking from `pos` in `direction`, with an extra
+    /// obstruction placed at `extra`.
+    fn jump(&self, pos: Position, direction: Direction, extra: Position) -> Option<Position> {
+        let stop = self.stops[pos.0 * self.cols + pos.1][direction.index()];
+        let Some(to_extra) = steps_ahead(pos, extra, direction) else {
+            return stop;
+        };
+
+        // The obstruction is hit first unless the regular stop comes before it
+        match stop {
+            Some(stop) if steps_ahead(pos, stop, direction).unwrap_or(0) < to_extra => Some(stop),
+            _ => {
+                let (dr, dc) = direction.to_delta();
+                let back = to_extra as isize - 1;
+                Some((
+                    (pos.0 as isize + dr * back) as usize,
+                    (pos.1 as isize + dc * back) as usize,
+                ))
+            }
         }
-    };
+    }
+}
+
+/// The number of steps from `from` to `to` walking in `direction`, if `to` is ahead.
+fn steps_ahead(from: Position, to: Position, direction: Direction) -> Option<usize> {
+    match direction {
+        Direction::Up if to.1 == from.1 && to.0 < from.0 => Some(from.0 - to.0),
+        Direction::Down if to.1 == from.1 && to.0 > from.0 => Some(to.0 - from.0),
+        Direction::Left if to.0 == from.0 && to.1 < from.1 => Some(from.1 - to.1),
+        Direction::Right if to.0 == from.0 && to.1 > from.1 => Some(to.1 - from.1),
+        _ => None,
+    }
+}
+
+/// Walk the guard from obstacle to obstacle with an extra obstruction in place.
+/// A loop is found when the guard stops at the same cell facing the same way twice.
+fn loops_with_obstruction(
+    table: &JumpTable,
+    start_pos: Position,
+    start_dir: Direction,
+    obstruction: Position,
+    policy: TurnPolicy,
+) -> bool {
+    let mut seen = HashSet::new();
+    let mut position = start_pos;
+    let mut direction = start_dir;
+
+    while let Some(stop) = table.jump(position, direction, obstruction) {
+        if !seen.insert((stop, direction)) {
+            return true;
+        }
+        position = stop;
+        direction = policy.turn(direction);
+    }
+    false
+}
+
+type Candidate = (Position, Position, Direction);
+
+/// Each cell on the guard's route, other than the start, together with the state the guard
+/// was in just before first stepping onto it. An obstruction anywhere else is never reached.
+/// Also returns whether the route is a loop without any extra obstruction.
+fn route_candidates(
+    grid: &[Vec<char>],
+    start_pos: Position,
+    start_dir: Direction,
+    policy: TurnPolicy,
+) -> (Vec<Candidate>, bool) {
+    let rows = grid.len() as isize;
+    let cols = grid[0].len() as isize;
+
+    let mut seen = HashSet::from([start_pos]);
+    let mut candidates = Vec::new();
+    let mut position = start_pos;
+    let mut direction = start_dir;
+    let mut states = HashSet::new();
 
     loop {
-        // Try to move forward in the current direction
+        if !states.insert((position, direction)) {
+            return (candidates, true);
+        }
+
         let (dr, dc) = direction.to_delta();
         let next_r = position.0 as isize + dr;
         let next_c = position.1 as isize + dc;
-
-        if next_r < 0 || next_r >= rows as isize || next_c < 0 || next_c >= cols as isize {
-            // Next step would leave the map, stop the simulation
-            break;
+        if next_r < 0 || next_r >= rows || next_c < 0 || next_c >= cols {
+            return (candidates, false);
         }
 
-        match is_free(next_r, next_c) {
-            Some(next_pos) => {
-                // The next position is free; move there
-                position = next_pos;
+        let next = (next_r as usize, next_c as usize);
+        if grid[next.0][next.1] == '#' {
+            direction = policy.turn(direction);
+        } else {
+            if seen.insert(next) {
+                candidates.push((next, position, direction));
+            }
+            position = next;
+        }
+    }
+}
 
-                // Check for loop:
-                // If we've been here before, check the direction pattern
-                let prev_dir = dir_grid[position.0][position.1];
-                if let Some(prev_dir) = prev_dir {
-                    // Tagging into a previous track
-                    if direction == prev_dir {
-                        has_loop = true;
-                        break;
-                    }
+/// The cells where one extra obstruction traps the guard in a loop, in route order.
+/// Only cells on the original route are tried, each trial starts just before the guard
+/// would first reach the obstruction, and the trials are split across threads.
+fn find_loop_obstructions(
+    grid: &[Vec<char>],
+    guard_pos: (usize, usize),
+    guard_dir: Direction,
+    policy: TurnPolicy,
+) -> Vec<Position> {
+    let table = JumpTable::new(grid);
+    let (candidates, route_loops) = route_candidates(grid, guard_pos, guard_dir, policy);
+
+    let threads = thread::available_parallelism().map_or(1, |n| n.get());
+    let chunk_size = candidates.len().div_ceil(threads).max(1);
+
+    let mut obstructions: Vec<Position> = thread::scope(|scope| {
+        let handles: Vec<_> = candidates
+            .chunks(chunk_size)
+            .map(|chunk| {
+                let table = &table;
+                scope.spawn(move || {
+                    chunk
+                        .iter()
+                        .filter(|&&(obstruction, pos, dir)| {
+                            loops_with_obstruction(table, pos, dir, obstruction, policy)
+                        })
+                        .map(|&(obstruction, _, _)| obstruction)
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().expect("loop search thread panicked"))
+            .collect()
+    });
+
+    // If the guard already loops, an obstruction off the route keeps it looping
+    if route_loops {
+        let on_route: HashSet<Position> = candidates.iter().map(|&(cell, _, _)| cell).collect();
+        for (r, row) in grid.iter().enumerate() {
+            for c in 0..row.len() {
+                if (r, c) != guard_pos && !on_route.contains(&(r, c)) {
+                    obstructions.push((r, c));
                 }
+            }
+        }
+    }
 
-                // Update direction grid for this cell
-                dir_grid[position.0][position.1] = Some(direction);
+    obstructions
+}
 
-                visited.insert(position);
-            }
-            None => {
-                // Turn right
-                direction = direction.turn_right();
+fn get_loop_count(
+    grid: &[Vec<char>],
+    guard_pos: (usize, usize),
+    guard_dir: Direction,
+    policy: TurnPolicy,
+) -> usize {
+    find_loop_obstructions(grid, guard_pos, guard_dir, policy).len()
+}
 
+/// The result of several guards patrolling at once.
+struct LockstepPatrol {
+    /// Cells visited by any guard
+    visited: HashSet<Position>,
+    looped: bool,
+}
+
+/// Move all guards one step per tick until every guard has left the map or the
+/// combined state repeats. A guard treats another guard like an obstacle: it turns
+/// instead of stepping onto a cell that is occupied at the start of the tick, or
+/// that another guard is stepping onto in the same tick.
+fn patrol_in_lockstep(
+    grid: &[Vec<char>],
+    guards: &[Guard],
+    obstruction: Option<Position>,
+    policy: TurnPolicy,
+) -> LockstepPatrol {
+    let rows = grid.len() as isize;
+    let cols = grid[0].len() as isize;
+
+    let mut active: Vec<Option<Guard>> = guards.iter().copied().map(Some).collect();
+    let mut visited: HashSet<Position> = guards.iter().map(|guard| guard.position).collect();
+    let mut seen = HashSet::from([active.clone()]);
+
+    while active.iter().any(Option::is_some) {
+        let occupied: HashSet<Position> = active.iter().flatten().map(|g| g.position).collect();
+
+        // The cell ahead of each guard, or None if it would leave the map
+        let ahead: Vec<Option<Position>> = active
+            .iter()
+            .map(|guard| {
+                let guard = (*guard)?;
+                let (dr, dc) = guard.direction.to_delta();
+                let next_r = guard.position.0 as isize + dr;
+                let next_c = guard.position.1 as isize + dc;
+                (next_r >= 0 && next_r < rows && next_c >= 0 && next_c < cols)
+                    .then_some((next_r as usize, next_c as usize))
+            })
+            .collect();
+
+        let mut claims: HashMap<Position, usize> = HashMap::new();
+        for next in ahead.iter().flatten() {
+            *claims.entry(*next).or_default() += 1;
+        }
+
+        for (slot, next) in active.iter_mut().zip(&ahead) {
+            let Some(guard) = slot else {
                 continue;
+            };
+            let Some(next) = *next else {
+                // Stepped off the map
+                *slot = None;
+                continue;
+            };
+
+            let blocked = grid[next.0][next.1] == '#'
+                || Some(next) == obstruction
+                || occupied.contains(&next)
+                || claims[&next] > 1;
+            if blocked {
+                guard.direction = policy.turn(guard.direction);
+            } else {
+                guard.position = next;
+                visited.insert(next);
             }
         }
+
+        if !seen.insert(active.clone()) {
+            return LockstepPatrol {
+                visited,
+                looped: true,
+            };
+        }
     }
 
-    // Mark visited positions with 'X' on the grid
-    for &(r, c) in &visited {
-        grid[r][c] = 'X';
+    LockstepPatrol {
+        visited,
+        looped: false,
     }
+}
 
-    (grid, visited.len(), has_loop)
+/// Part one for any number of guards: the number of distinct cells visited.
+fn count_visited(grid: &[Vec<char>], guards: &[Guard], policy: TurnPolicy) -> usize {
+    match guards {
+        [guard] => simulate(grid.to_vec(), guard.position, guard.direction, policy).1,
+        _ => patrol_in_lockstep(grid, guards, None, policy).visited.len(),
+    }
 }
 
-fn get_loop_count(grid: &Vec<Vec<char>>, guard_pos: (usize, usize), guard_dir: Direction) -> usize {
-    let mut loop_count = 0;
-    for r in 0..grid.len() {
-        println!(
-            "Processing row {} of {}, loop_count={}",
-            r,
-            grid.len(),
-            loop_count
-        );
-        for c in 0..grid[0].len() {
-            if !(r == guard_pos.0 && c == guard_pos.1) {
-                let mut test_grid = grid.clone();
-                test_grid[r][c] = '#';
-                let (_final_grid, _count, has_loop) = simulate(test_grid, guard_pos, guard_dir);
-                if has_loop {
-                    loop_count += 1;
-                }
-            }
+/// Part two for any number of guards: the number of cells where one extra obstruction
+/// keeps the guards from all leaving. A single guard uses the fast search. With several
+/// guards the candidates are still the visited cells, but each trial runs from the start
+/// because the guards affect each other.
+fn count_loop_obstructions(grid: &[Vec<char>], guards: &[Guard], policy: TurnPolicy) -> usize {
+    if let [guard] = guards {
+        return get_loop_count(grid, guard.position, guard.direction, policy);
+    }
+
+    let starts: HashSet<Position> = guards.iter().map(|guard| guard.position).collect();
+    let base = patrol_in_lockstep(grid, guards, None, policy);
+    let candidates: Vec<Position> = base
+        .visited
+        .iter()
+        .copied()
+        .filter(|cell| !starts.contains(cell))
+        .collect();
+
+    // If the guards already loop, an obstruction off their routes keeps them looping
+    let off_route = if base.looped {
+        grid.len() * grid[0].len() - starts.len() - candidates.len()
+    } else {
+        0
+    };
+
+    let threads = thread::available_parallelism().map_or(1, |n| n.get());
+    let chunk_size = candidates.len().div_ceil(threads).max(1);
+
+    let on_route: usize = thread::scope(|scope| {
+        let handles: Vec<_> = candidates
+            .chunks(chunk_size)
+            .map(|chunk| {
+                scope.spawn(move || {
+                    chunk
+                        .iter()
+                        .filter(|&&cell| {
+                            patrol_in_lockstep(grid, guards, Some(cell), policy).looped
+                        })
+                        .count()
+                })
+            })
+            .collect();
+
+        handles
+            .into_iter()
+            .map(|handle| handle.join().expect("loop search thread panicked"))
+            .sum()
+    });
+
+    on_route + off_route
+}
+
+/// Render the loop caused by an obstruction in the style of the puzzle text:
+/// '|' and '-' for straight moves, '+' for turns and crossings, and 'O' for the obstruction.
+fn render_loop(grid: &[Vec<char>], patrol: &Patrol, obstruction: Position) -> String {
+    let mut annotated = grid.to_vec();
+    for cell in annotated.iter_mut().flatten() {
+        if *cell != '#' {
+            *cell = '.';
         }
     }
-    loop_count
+
+    for step in patrol.cycle() {
+        let (r, c) = step.position;
+        let mark = match step.direction {
+            _ if step.turned => '+',
+            Direction::Up | Direction::Down => '|',
+            Direction::Left | Direction::Right => '-',
+        };
+        annotated[r][c] = match annotated[r][c] {
+            '.' => mark,
+            current if current == mark => mark,
+            _ => '+',
+        };
+    }
+    annotated[obstruction.0][obstruction.1] = 'O';
+
+    render_grid(&annotated)
+}
+
+fn render_grid(grid: &[Vec<char>]) -> String {
+    grid.iter()
+        .map(|row| row.iter().collect::<String>())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Render the patrol after `upto` steps: visited cells are 'X' and the guard
+/// is drawn facing its current direction.
+fn render_frame(grid: &[Vec<char>], patrol: &Patrol, upto: usize) -> String {
+    let mut frame = grid.to_vec();
+    for step in &patrol.steps[..=upto] {
+        frame[step.position.0][step.position.1] = 'X';
+    }
+    let current = patrol.steps[upto];
+    frame[current.position.0][current.position.1] = current.direction.glyph();
+    render_grid(&frame)
+}
+
+/// Replay the patrol in the terminal, redrawing the grid after every step.
+fn animate(grid: &[Vec<char>], patrol: &Patrol, delay: Duration) {
+    for upto in 0..patrol.steps.len() {
+        // Clear the screen and move the cursor to the top left
+        print!("\x1b[2J\x1b[H");
+        println!("{}", render_frame(grid, patrol, upto));
+        println!("Step {} of {}", upto, patrol.steps.len() - 1);
+        thread::sleep(delay);
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use aoc2024::rng::Lcg;
 
     const TEST_DATA: &str = "\
 ....#.....
@@ -190,8 +743,13 @@ mod tests {
 
     #[test]
     fn test_part_one() {
-        let (grid, guard_pos, guard_dir) = parse_input(TEST_DATA).unwrap();
-        let (_final_grid, count, has_loop) = simulate(grid, guard_pos, guard_dir);
+        let (grid, guards) = parse_input(TEST_DATA).unwrap();
+        let Guard {
+            position: guard_pos,
+            direction: guard_dir,
+        } = guards[0];
+        let (_final_grid, count, has_loop) =
+            simulate(grid, guard_pos, guard_dir, TurnPolicy::Right);
 
         let expected_count = 41;
         assert_eq!(count, expected_count);
@@ -202,10 +760,179 @@ mod tests {
 
     #[test]
     fn test_part_twp() {
-        let (grid, guard_pos, guard_dir) = parse_input(TEST_DATA).unwrap();
-        let loop_count = get_loop_count(&grid, guard_pos, guard_dir);
+        let (grid, guards) = parse_input(TEST_DATA).unwrap();
+        let Guard {
+            position: guard_pos,
+            direction: guard_dir,
+        } = guards[0];
+        let loop_count = get_loop_count(&grid, guard_pos, guard_dir, TurnPolicy::Right);
 
         let expected_loop_count = 6;
         assert_eq!(loop_count, expected_loop_count);
     }
+
+    /// The original search: rerun the full simulation with an obstruction in every cell.
+    fn get_loop_count_brute_force(
+        grid: &[Vec<char>],
+        guard_pos: (usize, usize),
+        guard_dir: Direction,
+        policy: TurnPolicy,
+    ) -> usize {
+        let mut loop_count = 0;
+        for r in 0..grid.len() {
+            for c in 0..grid[0].len() {
+                if !(r == guard_pos.0 && c == guard_pos.1) {
+                    let mut test_grid = grid.to_vec();
+                    test_grid[r][c] = '#';
+                    let (_final_grid, _count, has_loop) =
+                        simulate(test_grid, guard_pos, guard_dir, policy);
+                    if has_loop {
+                        loop_count += 1;
+                    }
+                }
+            }
+        }
+        loop_count
+    }
+
+    #[test]
+    fn test_loop_count_matches_brute_force() {
+        let (grid, guards) = parse_input(TEST_DATA).unwrap();
+        let Guard {
+            position: guard_pos,
+            direction: guard_dir,
+        } = guards[0];
+        assert_eq!(
+            get_loop_count(&grid, guard_pos, guard_dir, TurnPolicy::Right),
+            get_loop_count_brute_force(&grid, guard_pos, guard_dir, TurnPolicy::Right)
+        );
+
+        // Pseudo-random grids with the guard placed in the middle
+        let mut rng = Lcg::new(6);
+        for _ in 0..20 {
+            let mut grid = vec![vec!['.'; 12]; 12];
+            for cell in grid.iter_mut().flatten() {
+                if rng.below(6) == 0 {
+                    *cell = '#';
+                }
+            }
+            let guard_pos = (6, 6);
+            grid[6][6] = '.';
+            for policy in [TurnPolicy::Left, TurnPolicy::Right, TurnPolicy::Reverse] {
+                assert_eq!(
+                    get_loop_count(&grid, guard_pos, Direction::Up, policy),
+                    get_loop_count_brute_force(&grid, guard_pos, Direction::Up, policy)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_patrol_trace() {
+        let (grid, guards) = parse_input(TEST_DATA).unwrap();
+        let Guard {
+            position: guard_pos,
+            direction: guard_dir,
+        } = guards[0];
+        let patrol = trace_patrol(&grid, guard_pos, guard_dir, None, TurnPolicy::Right);
+
+        assert_eq!(patrol.loop_start, None);
+        assert_eq!(patrol.visited().len(), 41);
+        assert_eq!(patrol.turns(), 10);
+        assert_eq!(
+            patrol.steps[4],
+            PatrolStep {
+                position: (2, 4),
+                direction: Direction::Up,
+                turned: false,
+            }
+        );
+
+        let frame = render_frame(&grid, &patrol, 6);
+        assert_eq!(frame.lines().nth(1), Some("....>....#"));
+    }
+
+    #[test]
+    fn test_render_loop() {
+        let (grid, guards) = parse_input(TEST_DATA).unwrap();
+        let Guard {
+            position: guard_pos,
+            direction: guard_dir,
+        } = guards[0];
+        let obstructions = find_loop_obstructions(&grid, guard_pos, guard_dir, TurnPolicy::Right);
+        assert!(obstructions.contains(&(6, 3)));
+
+        let patrol = trace_patrol(&grid, guard_pos, guard_dir, Some((6, 3)), TurnPolicy::Right);
+        let expected_render = "\
+....#.....
+....+---+#
+....|...|.
+..#.|...|.
+....|..#|.
+....|...|.
+.#.O+---+.
+........#.
+#.........
+......#...";
+        assert_eq!(render_loop(&grid, &patrol, (6, 3)), expected_render);
+    }
+
+    #[test]
+    fn test_turn_policies() {
+        let (grid, guards) = parse_input(TEST_DATA).unwrap();
+
+        // Turning back walks the guard down column 4 and off the bottom
+        assert_eq!(count_visited(&grid, &guards, TurnPolicy::Reverse), 9);
+
+        // Lockstep with one guard is the same as the single guard patrol
+        for policy in [TurnPolicy::Left, TurnPolicy::Right, TurnPolicy::Reverse] {
+            let lockstep = patrol_in_lockstep(&grid, &guards, None, policy);
+            assert_eq!(
+                lockstep.visited.len(),
+                count_visited(&grid, &guards, policy)
+            );
+        }
+    }
+
+    #[test]
+    fn test_guards_collide() {
+        // Both guards want the middle cell, so both turn right instead
+        let (grid, guards) = parse_input(".....\n.>.<.\n.....").unwrap();
+        assert_eq!(
+            guards,
+            vec![
+                Guard {
+                    position: (1, 1),
+                    direction: Direction::Right,
+                },
+                Guard {
+                    position: (1, 3),
+                    direction: Direction::Left,
+                },
+            ]
+        );
+
+        let lockstep = patrol_in_lockstep(&grid, &guards, None, TurnPolicy::Right);
+        assert!(!lockstep.looped);
+        assert_eq!(
+            lockstep.visited,
+            HashSet::from([(1, 1), (1, 3), (2, 1), (0, 3)])
+        );
+    }
+
+    #[test]
+    fn test_multiple_guards_loop_count() {
+        let (grid, mut guards) = parse_input(TEST_DATA).unwrap();
+
+        // A second guard that walks straight off the map changes nothing
+        guards.push(Guard {
+            position: (0, 0),
+            direction: Direction::Left,
+        });
+        assert_eq!(
+            count_loop_obstructions(&grid, &guards, TurnPolicy::Right),
+            6
+        );
+        assert_eq!(count_visited(&grid, &guards, TurnPolicy::Right), 42);
+    }
 }
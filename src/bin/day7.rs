@@ -1,4 +1,5 @@
 use aoc2024::fetch_or_load_input;
+use std::env;
 use std::error::Error;
 
 fn main() -> Result<(), Box<dyn Error>> {
@@ -13,14 +14,226 @@ fn main() -> Result<(), Box<dyn Error>> {
     let sum = solve(&input, use_concat)?;
     println!("Total sum part two: {}", sum);
 
+    // `day7 --ops "+ * - ^"` solves with a custom set of operators
+    let args: Vec<String> = env::args().collect();
+    let ops = match args.iter().position(|arg| arg == "--ops") {
+        Some(idx) => {
+            let symbols = args.get(idx + 1).ok_or("Missing operators after --ops")?;
+            let ops = symbols
+                .split_whitespace()
+                .map(Operator::from_symbol)
+                .collect::<Result<Vec<_>, _>>()?;
+            let sum = solve_with(&input, &ops)?;
+            println!("Total sum with {}: {}", symbols, sum);
+            ops
+        }
+        None => operators(use_concat),
+    };
+
+    // `day7 --explain` shows a solution and the number of solutions for each equation
+    if args.iter().any(|arg| arg == "--explain") {
+        for line in input.lines().filter(|line| !line.trim().is_empty()) {
+            let (result_val, nums) = parse_line(line)?;
+            match explain_equation(result_val, &nums, &ops) {
+                Ok(report) => println!("{}", report),
+                Err(e) => println!("{}", e),
+            }
+        }
+    }
+
     Ok(())
 }
 
-#[derive(Copy, Clone, Debug)]
-enum Op {
-    Plus,
-    Mult,
-    Concat,
+/// Evaluate `left op right`.
+type ApplyFn = fn(i64, i64) -> Evaluation;
+
+/// Given a target and the right operand, the left operands that produce the target.
+type InverseFn = fn(i64, i64) -> Preimage;
+
+/// The result of applying an operator to two operands.
+#[derive(Debug, PartialEq)]
+enum Evaluation {
+    Value(i64),
+    /// The result does not fit in an i64
+    Overflow,
+    /// The operator has no result for these operands, as in `2 ^ -1`
+    Undefined,
+}
+
+impl Evaluation {
+    /// A result where None means it overflowed.
+    fn from_checked(value: Option<i64>) -> Self {
+        match value {
+            Some(value) => Evaluation::Value(value),
+            None => Evaluation::Overflow,
+        }
+    }
+}
+
+/// The left operands that could have produced a value.
+#[derive(Debug, PartialEq)]
+enum Preimage {
+    Values(Vec<i64>),
+    /// Every left operand works, as in `x * 0 = 0`
+    Any,
+    /// A left operand exists but does not fit in an i64
+    Overflow,
+}
+
+impl Preimage {
+    fn none() -> Self {
+        Preimage::Values(Vec::new())
+    }
+
+    /// A single left operand, where None means it overflowed.
+    fn from_checked(value: Option<i64>) -> Self {
+        match value {
+            Some(value) => Preimage::Values(vec![value]),
+            None => Preimage::Overflow,
+        }
+    }
+}
+
+/// A binary operator that knows how to undo itself.
+#[derive(Copy, Clone)]
+struct Operator {
+    symbol: &'static str,
+    apply: ApplyFn,
+    inverse: InverseFn,
+}
+
+impl Operator {
+    const PLUS: Operator = Operator {
+        symbol: "+",
+        apply: |a, b| Evaluation::from_checked(a.checked_add(b)),
+        inverse: |target, b| Preimage::from_checked(target.checked_sub(b)),
+    };
+
+    const MULT: Operator = Operator {
+        symbol: "*",
+        apply: |a, b| Evaluation::from_checked(a.checked_mul(b)),
+        inverse: |target, b| match b {
+            0 if target == 0 => Preimage::Any,
+            0 => Preimage::none(),
+            _ if target % b != 0 => Preimage::none(),
+            _ => Preimage::from_checked(target.checked_div(b)),
+        },
+    };
+
+    const CONCAT: Operator = Operator {
+        symbol: "||",
+        apply: |a, b| {
+            if b < 0 {
+                return Evaluation::Undefined;
+            }
+            let shift = concat_shift(b);
+            Evaluation::from_checked(shift.and_then(|shift| a.checked_mul(shift)?.checked_add(b)))
+        },
+        // The target must end with the digits of b
+        inverse: |target, b| {
+            let Some(shift) = concat_shift(b) else {
+                return Preimage::none();
+            };
+            match target.checked_sub(b) {
+                Some(rest) if rest % shift == 0 => Preimage::Values(vec![rest / shift]),
+                Some(_) => Preimage::none(),
+                None => Preimage::Overflow,
+            }
+        },
+    };
+
+    const MINUS: Operator = Operator {
+        symbol: "-",
+        apply: |a, b| Evaluation::from_checked(a.checked_sub(b)),
+        inverse: |target, b| Preimage::from_checked(target.checked_add(b)),
+    };
+
+    const POW: Operator = Operator {
+        symbol: "^",
+        apply: |a, b| match u32::try_from(b) {
+            Ok(exponent) => Evaluation::from_checked(a.checked_pow(exponent)),
+            // Negative exponents give fractions, not integers
+            Err(_) if b < 0 => Evaluation::Undefined,
+            Err(_) => Evaluation::Overflow,
+        },
+        inverse: |target, b| {
+            let Ok(exponent) = u32::try_from(b) else {
+                return Preimage::none();
+            };
+            match exponent {
+                0 if target == 1 => Preimage::Any,
+                0 => Preimage::none(),
+                _ if target >= 0 => match integer_root(target, exponent) {
+                    Some(0) => Preimage::Values(vec![0]),
+                    Some(root) if exponent % 2 == 0 => Preimage::Values(vec![root, -root]),
+                    Some(root) => Preimage::Values(vec![root]),
+                    None => Preimage::none(),
+                },
+                _ if exponent % 2 == 1 => {
+                    // i64::MIN has no odd integer root that fits, -(2^63) = (-2)^63 aside
+                    match target.checked_neg().and_then(|t| integer_root(t, exponent)) {
+                        Some(root) => Preimage::Values(vec![-root]),
+                        None if target == i64::MIN && exponent == 63 => Preimage::Values(vec![-2]),
+                        None => Preimage::none(),
+                    }
+                }
+                _ => Preimage::none(),
+            }
+        },
+    };
+
+    fn from_symbol(symbol: &str) -> Result<Operator, Box<dyn Error>> {
+        [
+            Operator::PLUS,
+            Operator::MULT,
+            Operator::CONCAT,
+            Operator::MINUS,
+            Operator::POW,
+        ]
+        .into_iter()
+        .find(|op| op.symbol == symbol)
+        .ok_or_else(|| format!("Unknown operator: '{}'", symbol).into())
+    }
+}
+
+/// The power of ten that shifts a number left to make room for the digits of `b`.
+fn concat_shift(b: i64) -> Option<i64> {
+    if b < 0 {
+        return None;
+    }
+    let digits = b.checked_ilog10().unwrap_or(0) + 1;
+    10_i64.checked_pow(digits)
+}
+
+/// The non-negative integer r with r^exponent == value, if there is one.
+/// Searched exactly, since a float estimate is off for values above 2^53.
+fn integer_root(value: i64, exponent: u32) -> Option<i64> {
+    if exponent == 0 || value < 0 {
+        return None;
+    }
+    if exponent == 1 {
+        return Some(value);
+    }
+
+    let (mut low, mut high) = (0, value);
+    while low <= high {
+        let mid = low + (high - low) / 2;
+        match mid.checked_pow(exponent) {
+            Some(power) if power == value => return Some(mid),
+            Some(power) if power < value => low = mid + 1,
+            _ => high = mid - 1,
+        }
+    }
+    None
+}
+
+/// The operators of the puzzle, with concatenation only in part two.
+fn operators(use_concat: bool) -> Vec<Operator> {
+    let mut ops = vec![Operator::PLUS, Operator::MULT];
+    if use_concat {
+        ops.push(Operator::CONCAT);
+    }
+    ops
 }
 
 fn parse_line(line: &str) -> Result<(i64, Vec<i64>), Box<dyn Error>> {
@@ -39,70 +252,189 @@ fn parse_line(line: &str) -> Result<(i64, Vec<i64>), Box<dyn Error>> {
     Ok((result_val, nums))
 }
 
-fn can_form_result(result: i64, nums: &[i64], use_concat: bool) -> bool {
-    if nums.is_empty() {
-        return false;
+/// Check whether the numbers, combined left to right, can produce the result.
+/// Fails if no way was found but some way needs values outside i64,
+/// since the answer can't be trusted then.
+fn can_form_result(result: i64, nums: &[i64], ops: &[Operator]) -> Result<bool, Box<dyn Error>> {
+    let mut overflow = false;
+    let found = find_witness(result, nums, ops, &mut overflow).is_some();
+    if !found && overflow {
+        return Err(format!("{}: cannot be decided within i64", result).into());
     }
-    if nums.len() == 1 {
-        return nums[0] == result;
+    Ok(found)
+}
+
+/// Find operators, in left to right order, that turn the numbers into the result.
+/// Works backwards from the result: the last number must have been combined by one of
+/// the operators, so undo it and recurse on the remaining numbers. Inverses that don't
+/// exist, such as a non-divisible product or a missing digit suffix, prune the search.
+/// Branches that need a value outside i64 are skipped and flagged in `overflow`.
+fn find_witness(
+    result: i64,
+    nums: &[i64],
+    ops: &[Operator],
+    overflow: &mut bool,
+) -> Option<Vec<Operator>> {
+    match nums.split_last() {
+        None => None,
+        Some((&first, [])) => (first == result).then(Vec::new),
+        Some((&last, rest)) => ops.iter().find_map(|op| {
+            let mut witness = match (op.inverse)(result, last) {
+                Preimage::Values(values) => values
+                    .into_iter()
+                    .find_map(|value| find_witness(value, rest, ops, overflow)),
+                Preimage::Any => find_evaluation(rest, ops, overflow),
+                Preimage::Overflow => {
+                    *overflow = true;
+                    None
+                }
+            }?;
+            witness.push(*op);
+            Some(witness)
+        }),
     }
+}
 
-    fn backtrack(nums: &[i64], result: i64, idx: usize, val: i64, use_concat: bool) -> bool {
-        if idx == nums.len() {
-            // All numbers used, check result
-            return val == result;
+/// Find operators that evaluate the numbers without overflow, to any value.
+fn find_evaluation(nums: &[i64], ops: &[Operator], overflow: &mut bool) -> Option<Vec<Operator>> {
+    fn forward(
+        val: i64,
+        rest: &[i64],
+        ops: &[Operator],
+        chosen: &mut Vec<Operator>,
+        overflow: &mut bool,
+    ) -> bool {
+        let Some((&next, rest)) = rest.split_first() else {
+            return true;
+        };
+        for op in ops {
+            match (op.apply)(val, next) {
+                Evaluation::Value(val) => {
+                    chosen.push(*op);
+                    if forward(val, rest, ops, chosen, overflow) {
+                        return true;
+                    }
+                    chosen.pop();
+                }
+                Evaluation::Overflow => *overflow = true,
+                Evaluation::Undefined => {}
+            }
         }
+        false
+    }
 
-        // Prune if already exceeded result
-        if val > result {
-            return false;
-        }
+    let (&first, rest) = nums.split_first()?;
+    let mut chosen = Vec::new();
+    forward(first, rest, ops, &mut chosen, overflow).then_some(chosen)
+}
 
-        let next_num = nums[idx];
+/// Count the operator assignments that turn the numbers into the result.
+fn count_solutions(result: i64, nums: &[i64], ops: &[Operator], overflow: &mut bool) -> u64 {
+    match nums.split_last() {
+        None => 0,
+        Some((&first, [])) => (first == result) as u64,
+        Some((&last, rest)) => ops
+            .iter()
+            .map(|op| match (op.inverse)(result, last) {
+                Preimage::Values(values) => values
+                    .into_iter()
+                    .map(|value| count_solutions(value, rest, ops, overflow))
+                    .sum(),
+                Preimage::Any => count_evaluations(rest, ops, overflow),
+                Preimage::Overflow => {
+                    *overflow = true;
+                    0
+                }
+            })
+            .sum(),
+    }
+}
 
-        // Try '+'
-        {
-            let new_val = apply_op(val, next_num, Op::Plus);
-            if backtrack(nums, result, idx + 1, new_val, use_concat) {
-                return true;
-            }
-        }
+/// Count the operator assignments that evaluate the numbers without overflow.
+fn count_evaluations(nums: &[i64], ops: &[Operator], overflow: &mut bool) -> u64 {
+    fn forward(val: i64, rest: &[i64], ops: &[Operator], overflow: &mut bool) -> u64 {
+        let Some((&next, rest)) = rest.split_first() else {
+            return 1;
+        };
+        ops.iter()
+            .map(|op| match (op.apply)(val, next) {
+                Evaluation::Value(val) => forward(val, rest, ops, overflow),
+                Evaluation::Overflow => {
+                    *overflow = true;
+                    0
+                }
+                Evaluation::Undefined => 0,
+            })
+            .sum()
+    }
 
-        // Try '*'
-        {
-            let new_val = apply_op(val, next_num, Op::Mult);
-            if backtrack(nums, result, idx + 1, new_val, use_concat) {
-                return true;
-            }
-        }
+    match nums.split_first() {
+        None => 0,
+        Some((&first, rest)) => forward(first, rest, ops, overflow),
+    }
+}
 
-        // Try '|' (concatenation)
-        if use_concat {
-            let new_val = apply_op(val, next_num, Op::Concat);
-            if backtrack(nums, result, idx + 1, new_val, use_concat) {
-                return true;
-            }
+/// One equation with an example solution and the number of solutions.
+struct EquationReport {
+    result: i64,
+    witness: Option<String>,
+    solutions: u64,
+}
+
+impl std::fmt::Display for EquationReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match &self.witness {
+            Some(witness) => write!(
+                f,
+                "{} = {} ({} solution{})",
+                self.result,
+                witness,
+                self.solutions,
+                if self.solutions == 1 { "" } else { "s" }
+            ),
+            None => write!(f, "{}: no solution", self.result),
         }
+    }
+}
 
-        false
+fn explain_equation(
+    result: i64,
+    nums: &[i64],
+    ops: &[Operator],
+) -> Result<EquationReport, Box<dyn Error>> {
+    let mut overflow = false;
+    let witness = find_witness(result, nums, ops, &mut overflow);
+    let solutions = count_solutions(result, nums, ops, &mut overflow);
+    if overflow {
+        return Err(format!(
+            "{}: some operator choices overflow i64, so the count is incomplete",
+            result
+        )
+        .into());
     }
 
-    backtrack(nums, result, 1, nums[0], use_concat)
+    Ok(EquationReport {
+        result,
+        witness: witness.map(|chosen| format_expression(nums, &chosen)),
+        solutions,
+    })
 }
 
-fn apply_op(val: i64, operand: i64, op: Op) -> i64 {
-    match op {
-        Op::Plus => val + operand,
-        Op::Mult => val * operand,
-        Op::Concat => {
-            let digits = operand.checked_ilog10().unwrap_or(0) + 1;
-            val * 10_i64.pow(digits) + operand
-        }
+/// Write out the numbers with the chosen operators between them, e.g. `81 + 40 * 27`.
+fn format_expression(nums: &[i64], chosen: &[Operator]) -> String {
+    let mut expression = nums[0].to_string();
+    for (num, op) in nums[1..].iter().zip(chosen) {
+        expression.push_str(&format!(" {} {}", op.symbol, num));
     }
+    expression
 }
 
 fn solve(input: &str, use_concat: bool) -> Result<i64, Box<dyn Error>> {
-    let mut total_sum = 0;
+    solve_with(input, &operators(use_concat))
+}
+
+fn solve_with(input: &str, ops: &[Operator]) -> Result<i64, Box<dyn Error>> {
+    let mut total_sum: i64 = 0;
     for line in input.lines() {
         let line = line.trim();
         if line.is_empty() {
@@ -110,8 +442,10 @@ fn solve(input: &str, use_concat: bool) -> Result<i64, Box<dyn Error>> {
         }
 
         let (result_val, nums) = parse_line(line)?;
-        if can_form_result(result_val, &nums, use_concat) {
-            total_sum += result_val;
+        if can_form_result(result_val, &nums, ops)? {
+            total_sum = total_sum
+                .checked_add(result_val)
+                .ok_or("Total sum overflows i64")?;
         }
     }
     Ok(total_sum)
@@ -120,6 +454,7 @@ fn solve(input: &str, use_concat: bool) -> Result<i64, Box<dyn Error>> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use aoc2024::rng::Lcg;
 
     const TEST_DATA: &str = "\
 190: 10 19
@@ -149,4 +484,131 @@ mod tests {
         let expected_sum = 11387;
         assert_eq!(sum, expected_sum);
     }
+
+    /// Try every assignment of operators, evaluating left to right.
+    fn forward_search(result: i64, nums: &[i64], ops: &[Operator]) -> bool {
+        fn forward(val: i64, rest: &[i64], result: i64, ops: &[Operator]) -> bool {
+            match rest.split_first() {
+                None => val == result,
+                Some((&next, rest)) => ops.iter().any(|op| match (op.apply)(val, next) {
+                    Evaluation::Value(val) => forward(val, rest, result, ops),
+                    _ => false,
+                }),
+            }
+        }
+        forward(nums[0], &nums[1..], result, ops)
+    }
+
+    #[test]
+    fn test_inverses() {
+        assert_eq!(
+            (Operator::CONCAT.inverse)(156, 6),
+            Preimage::Values(vec![15])
+        );
+        assert_eq!((Operator::CONCAT.inverse)(156, 7), Preimage::none());
+        assert_eq!((Operator::MULT.inverse)(0, 0), Preimage::Any);
+        assert_eq!((Operator::MINUS.inverse)(i64::MAX, 1), Preimage::Overflow);
+        assert_eq!(
+            (Operator::POW.inverse)(81, 4),
+            Preimage::Values(vec![3, -3])
+        );
+        assert_eq!((Operator::POW.inverse)(-27, 3), Preimage::Values(vec![-3]));
+    }
+
+    #[test]
+    fn test_integer_root_is_exact() {
+        // Above 2^53 an f64 can no longer represent every integer
+        let big = (1 << 62) + 7;
+        assert_eq!(integer_root(big, 1), Some(big));
+        assert_eq!(integer_root(big, 2), None);
+
+        let root = i64::from(i32::MAX);
+        assert_eq!(integer_root(root * root, 2), Some(root));
+        assert_eq!(integer_root(root * root + 1, 2), None);
+        assert_eq!(integer_root(root * root - 1, 2), None);
+
+        assert_eq!(integer_root(3_i64.pow(39), 39), Some(3));
+        assert_eq!(integer_root(i64::MAX, 63), None);
+        assert_eq!(integer_root(0, 5), Some(0));
+        assert_eq!(integer_root(1, 62), Some(1));
+    }
+
+    #[test]
+    fn test_matches_forward_search() {
+        let all_ops = [
+            Operator::PLUS,
+            Operator::MULT,
+            Operator::CONCAT,
+            Operator::MINUS,
+            Operator::POW,
+        ];
+
+        let mut rng = Lcg::new(7);
+        let mut next = |bound: u64| rng.below(bound);
+
+        for _ in 0..500 {
+            let nums: Vec<i64> = (0..2 + next(4)).map(|_| next(12) as i64 - 2).collect();
+            let ops: Vec<Operator> = all_ops.iter().copied().filter(|_| next(3) != 0).collect();
+            let result = next(200) as i64 - 50;
+
+            let mut overflow = false;
+            assert_eq!(
+                find_witness(result, &nums, &ops, &mut overflow).is_some(),
+                forward_search(result, &nums, &ops),
+                "{} from {:?}",
+                result,
+                nums
+            );
+        }
+    }
+
+    #[test]
+    fn test_witness_and_count() {
+        let ops = operators(false);
+        let report = explain_equation(3267, &[81, 40, 27], &ops).unwrap();
+        assert_eq!(report.witness.as_deref(), Some("81 * 40 + 27"));
+        assert_eq!(report.solutions, 2);
+
+        let ops = operators(true);
+        let report = explain_equation(7290, &[6, 8, 6, 15], &ops).unwrap();
+        assert_eq!(report.to_string(), "7290 = 6 * 8 || 6 * 15 (1 solution)");
+
+        let report = explain_equation(83, &[17, 5], &ops).unwrap();
+        assert_eq!(report.to_string(), "83: no solution");
+
+        // 0 * 0 makes the prefix irrelevant, so every choice for it counts
+        let report = explain_equation(0, &[3, 4, 0], &ops).unwrap();
+        assert_eq!(report.solutions, 3);
+    }
+
+    #[test]
+    fn test_overflow_is_reported() {
+        let ops = [Operator::PLUS, Operator::MINUS];
+        let max = i64::MAX;
+
+        // MAX - 1 + 1 works, MAX + 1 - 1 would overflow on the way
+        assert!(can_form_result(max, &[max, 1, 1], &ops).unwrap());
+        assert!(explain_equation(max, &[max, 1, 1], &ops).is_err());
+
+        // Concatenation past i64 is never wrapped around into a false match. Working
+        // backwards from the target, the search never needs such values at all.
+        let ops = operators(true);
+        let big = 999_999_999_999;
+        let report = explain_equation(5, &[big, big], &ops).unwrap();
+        assert_eq!(report.solutions, 0);
+    }
+
+    #[test]
+    fn test_undefined_is_not_overflow() {
+        assert_eq!((Operator::POW.apply)(2, -1), Evaluation::Undefined);
+        assert_eq!((Operator::POW.apply)(2, 63), Evaluation::Overflow);
+        assert_eq!((Operator::POW.apply)(-2, 3), Evaluation::Value(-8));
+        assert_eq!((Operator::CONCAT.apply)(1, -2), Evaluation::Undefined);
+
+        // `* 0` makes every evaluation of 2 and -1 count, and 2 ^ -1 is simply not one
+        let ops = [Operator::MULT, Operator::POW];
+        let report = explain_equation(0, &[2, -1, 0], &ops).unwrap();
+        assert_eq!(report.witness.as_deref(), Some("2 * -1 * 0"));
+        assert_eq!(report.solutions, 1);
+    }
 }
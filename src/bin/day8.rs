@@ -1,5 +1,6 @@
 use aoc2024::fetch_or_load_input;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashSet};
+use std::env;
 use std::error::Error;
 
 fn main() -> Result<(), Box<dyn Error>> {
@@ -8,12 +9,27 @@ fn main() -> Result<(), Box<dyn Error>> {
 
     let grid = parse_input(&input);
 
-    let count = solve(&grid, false);
+    let count = solve(&grid, &AntinodeRule::PART_ONE);
     println!("Total count part one: {}", count);
 
-    let count_part_two = solve(&grid, true);
+    let count_part_two = solve(&grid, &AntinodeRule::PART_TWO);
     println!("Total count part two: {}", count_part_two);
 
+    // `day8 --rule ratios:3:1,1:3 [--render]` counts antinodes under another rule
+    // and optionally draws each frequency
+    let args: Vec<String> = env::args().collect();
+    if let Some(idx) = args.iter().position(|arg| arg == "--rule") {
+        let rule = AntinodeRule::parse(args.get(idx + 1).ok_or("Missing rule after --rule")?)?;
+        let by_frequency = antinodes_by_frequency(&grid, &rule);
+        for (frequency, antinodes) in &by_frequency {
+            println!("Frequency {}: {} antinodes", frequency, antinodes.len());
+            if args.iter().any(|arg| arg == "--render") {
+                println!("{}\n", render_frequency(&grid, *frequency, antinodes));
+            }
+        }
+        println!("Total count with {:?}: {}", rule, solve(&grid, &rule));
+    }
+
     Ok(())
 }
 
@@ -21,68 +37,182 @@ fn parse_input(input: &str) -> Vec<Vec<char>> {
     input.lines().map(|line| line.chars().collect()).collect()
 }
 
-fn solve(grid: &[Vec<char>], part_two: bool) -> usize {
-    let rows = grid.len();
-    if rows == 0 {
-        return 0;
-    }
-    let cols = grid[0].len();
+type Position = (usize, usize);
 
-    let (min_idx, max_idx) = if part_two {
-        (0, rows.max(cols))
-    } else {
-        (1, 2)
-    };
+/// Where antinodes appear for a pair of antennas `a` and `b` of the same frequency.
+/// Points are on the line through the pair, at `a + t * (b - a)` for some `t`.
+#[derive(Clone, Debug, PartialEq)]
+enum AntinodeRule {
+    /// Points outside the pair where one antenna is `k` times as far away as the other
+    Harmonic(u32),
+    /// Every grid point on the line through the pair
+    Collinear,
+    /// Points where the distances to `a` and `b` are in one of the ratios `m:n`,
+    /// both between and outside the pair
+    Ratios(Vec<(u32, u32)>),
+}
 
-    // Map each character to its list of positions
-    let mut positions_map: HashMap<char, Vec<(usize, usize)>> = HashMap::new();
+impl AntinodeRule {
+    const PART_ONE: AntinodeRule = AntinodeRule::Harmonic(2);
+    const PART_TWO: AntinodeRule = AntinodeRule::Collinear;
 
-    for x in 0..rows {
-        for y in 0..cols {
-            let ch = grid[x][y];
-            if ch != '.' {
-                positions_map.entry(ch).or_default().push((x, y));
+    /// Parse `harmonic`, `harmonic:K`, `collinear` or `ratios:M:N,M:N,...`.
+    fn parse(text: &str) -> Result<AntinodeRule, Box<dyn Error>> {
+        let (name, params) = text.split_once(':').unwrap_or((text, ""));
+        match (name, params) {
+            ("harmonic", "") => Ok(AntinodeRule::Harmonic(2)),
+            ("harmonic", k) => Ok(AntinodeRule::Harmonic(k.parse()?)),
+            ("collinear", "") => Ok(AntinodeRule::Collinear),
+            ("ratios", list) => {
+                let ratios = list
+                    .split(',')
+                    .map(|ratio| {
+                        let (m, n) = ratio
+                            .split_once(':')
+                            .ok_or_else(|| format!("Invalid ratio: '{}'", ratio))?;
+                        Ok((m.trim().parse()?, n.trim().parse()?))
+                    })
+                    .collect::<Result<Vec<_>, Box<dyn Error>>>()?;
+                Ok(AntinodeRule::Ratios(ratios))
             }
+            _ => Err(format!("Unknown antinode rule: '{}'", text).into()),
         }
     }
 
-    let mut antinodes = HashSet::new();
-
-    // For each character, consider all pairs of distinct positions
-    for (_, positions) in &positions_map {
-        let len = positions.len();
-        // Generate all pairs (p1,p2), p1 < p2 to avoid duplicates
-        for i in 0..len {
-            for j in i + 1..len {
-                let (x1, y1) = positions[i];
-                let (x2, y2) = positions[j];
-                let dx = x2 as isize - x1 as isize;
-                let dy = y2 as isize - y1 as isize;
-
-                for n in min_idx..max_idx {
-                    let ax = x2 as isize + n as isize * dx;
-                    let ay = y2 as isize + n as isize * dy;
-                    if ax >= 0 && ax < rows as isize && ay >= 0 && ay < cols as isize {
-                        antinodes.insert((ax as usize, ay as usize));
+    /// The antinodes of one pair of antennas that lie inside a `rows` x `cols` grid.
+    fn antinodes(&self, a: Position, b: Position, rows: usize, cols: usize) -> Vec<Position> {
+        let start = (a.0 as i64, a.1 as i64);
+        let delta = (b.0 as i64 - start.0, b.1 as i64 - start.1);
+        let in_grid = |(x, y): (i64, i64)| {
+            (x >= 0 && x < rows as i64 && y >= 0 && y < cols as i64)
+                .then_some((x as usize, y as usize))
+        };
+
+        // Each ratio is given as t = num / den
+        let mut fractions: Vec<(i64, i64)> = Vec::new();
+        match self {
+            AntinodeRule::Collinear => {
+                // Step by the smallest lattice vector along the line
+                let divisor = gcd(delta.0, delta.1).max(1);
+                let step = (delta.0 / divisor, delta.1 / divisor);
+
+                let mut points = Vec::new();
+                for direction in [1, -1] {
+                    let mut point = if direction == 1 {
+                        start
                     } else {
-                        break;
+                        (start.0 - step.0, start.1 - step.1)
+                    };
+                    while let Some(position) = in_grid(point) {
+                        points.push(position);
+                        point = (point.0 + direction * step.0, point.1 + direction * step.1);
                     }
                 }
-
-                for n in min_idx..max_idx {
-                    let ax = x1 as isize - n as isize * dx;
-                    let ay = y1 as isize - n as isize * dy;
-                    if ax >= 0 && ax < rows as isize && ay >= 0 && ay < cols as isize {
-                        antinodes.insert((ax as usize, ay as usize));
-                    } else {
-                        break;
+                return points;
+            }
+            AntinodeRule::Harmonic(k) => {
+                let k = *k as i64;
+                if k != 1 {
+                    fractions.push((k, k - 1));
+                    fractions.push((-1, k - 1));
+                }
+            }
+            AntinodeRule::Ratios(ratios) => {
+                for &(m, n) in ratios {
+                    let (m, n) = (m as i64, n as i64);
+                    if m + n > 0 {
+                        fractions.push((m, m + n));
+                    }
+                    if m != n {
+                        fractions.push((m, m - n));
                     }
                 }
             }
         }
+
+        fractions
+            .into_iter()
+            .filter_map(|(num, den)| lattice_point(start, delta, num, den))
+            .filter_map(in_grid)
+            .collect()
+    }
+}
+
+fn gcd(a: i64, b: i64) -> i64 {
+    if b == 0 {
+        a.abs()
+    } else {
+        gcd(b, a % b)
     }
+}
 
-    antinodes.len()
+/// The point `start + delta * num / den`, if it has integer coordinates.
+fn lattice_point(start: (i64, i64), delta: (i64, i64), num: i64, den: i64) -> Option<(i64, i64)> {
+    let (x, y) = (delta.0 * num, delta.1 * num);
+    (x % den == 0 && y % den == 0).then(|| (start.0 + x / den, start.1 + y / den))
+}
+
+/// Map each frequency to the positions of its antennas.
+fn antennas_by_frequency(grid: &[Vec<char>]) -> BTreeMap<char, Vec<Position>> {
+    let mut antennas: BTreeMap<char, Vec<Position>> = BTreeMap::new();
+    for (x, row) in grid.iter().enumerate() {
+        for (y, &ch) in row.iter().enumerate() {
+            if ch != '.' {
+                antennas.entry(ch).or_default().push((x, y));
+            }
+        }
+    }
+    antennas
+}
+
+/// The antinodes of every frequency, from all pairs of its antennas.
+fn antinodes_by_frequency(
+    grid: &[Vec<char>],
+    rule: &AntinodeRule,
+) -> BTreeMap<char, HashSet<Position>> {
+    let rows = grid.len();
+    let cols = grid.first().map_or(0, |row| row.len());
+
+    antennas_by_frequency(grid)
+        .into_iter()
+        .map(|(frequency, positions)| {
+            let mut antinodes = HashSet::new();
+            for (i, &a) in positions.iter().enumerate() {
+                for &b in &positions[i + 1..] {
+                    antinodes.extend(rule.antinodes(a, b, rows, cols));
+                }
+            }
+            (frequency, antinodes)
+        })
+        .collect()
+}
+
+/// Count the distinct grid cells that are an antinode of any frequency.
+fn solve(grid: &[Vec<char>], rule: &AntinodeRule) -> usize {
+    antinodes_by_frequency(grid, rule)
+        .into_values()
+        .flatten()
+        .collect::<HashSet<_>>()
+        .len()
+}
+
+/// Render the antennas of one frequency with its antinodes marked as '#'.
+/// Antennas that are also antinodes are still shown as antennas.
+fn render_frequency(grid: &[Vec<char>], frequency: char, antinodes: &HashSet<Position>) -> String {
+    grid.iter()
+        .enumerate()
+        .map(|(x, row)| {
+            row.iter()
+                .enumerate()
+                .map(|(y, &ch)| match ch {
+                    _ if ch == frequency => ch,
+                    _ if antinodes.contains(&(x, y)) => '#',
+                    _ => '.',
+                })
+                .collect::<String>()
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
 }
 
 #[cfg(test)]
@@ -106,7 +236,7 @@ mod tests {
     #[test]
     fn test_part_one() {
         let grid = parse_input(TEST_DATA);
-        let count = solve(&grid, false);
+        let count = solve(&grid, &AntinodeRule::PART_ONE);
 
         let expected_count = 14;
         assert_eq!(count, expected_count);
@@ -115,9 +245,56 @@ mod tests {
     #[test]
     fn test_part_two() {
         let grid = parse_input(TEST_DATA);
-        let count = solve(&grid, true);
+        let count = solve(&grid, &AntinodeRule::PART_TWO);
 
         let expected_count = 34;
         assert_eq!(count, expected_count);
     }
+
+    #[test]
+    fn test_collinear_steps_are_reduced() {
+        // The antennas are 2 apart on both axes, so (1, 1) lies between them
+        let rule = AntinodeRule::Collinear;
+        let mut antinodes = rule.antinodes((0, 0), (2, 2), 4, 4);
+        antinodes.sort();
+
+        assert_eq!(antinodes, vec![(0, 0), (1, 1), (2, 2), (3, 3)]);
+    }
+
+    #[test]
+    fn test_ratio_rules() {
+        // 2:1 between the pair lands on the lattice when the delta divides by 3
+        let rule = AntinodeRule::parse("ratios:2:1,1:2").unwrap();
+        let mut antinodes = rule.antinodes((3, 3), (6, 6), 12, 12);
+        antinodes.sort();
+        assert_eq!(antinodes, vec![(0, 0), (4, 4), (5, 5), (9, 9)]);
+
+        let rule = AntinodeRule::parse("harmonic:3").unwrap();
+        let mut antinodes = rule.antinodes((4, 4), (6, 6), 12, 12);
+        antinodes.sort();
+        assert_eq!(antinodes, vec![(3, 3), (7, 7)]);
+    }
+
+    #[test]
+    fn test_render_frequency() {
+        let grid = parse_input(TEST_DATA);
+        let by_frequency = antinodes_by_frequency(&grid, &AntinodeRule::PART_ONE);
+        assert_eq!(by_frequency[&'A'].len(), 5);
+
+        let render = render_frequency(&grid, 'A', &by_frequency[&'A']);
+        let expected_render = "\
+............
+...#........
+....#.......
+............
+............
+......A.....
+............
+.......#....
+........A...
+.........A..
+..........#.
+..........#.";
+        assert_eq!(render, expected_render);
+    }
 }
@@ -1,5 +1,10 @@
 use aoc2024::fetch_or_load_input;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::env;
 use std::error::Error;
+use std::thread;
+use std::time::Duration;
 
 fn main() -> Result<(), Box<dyn Error>> {
     let day = 9;
@@ -10,18 +15,55 @@ fn main() -> Result<(), Box<dyn Error>> {
         let initial_array = build_initial_array(&files, &frees);
         let total_length: usize = files.iter().map(|v| *v as usize).sum();
 
-        let final_array = fill_free_spaces_from_back(&initial_array, total_length);
+        let final_array = fill_free_spaces_from_back(&initial_array, total_length, None);
         let checksum = compute_checksum(&final_array);
         println!("Checksum part one: {}", checksum);
     }
 
     {
-        let mut final_array = build_initial_array(&files, &frees);
-        move_files_left(&files, &mut final_array);
-        let checksum_part_two = compute_checksum(&final_array);
+        let file_spans = compact_files(&files, &frees, None);
+        let checksum_part_two = compute_span_checksum(&file_spans);
         println!("Checksum part two: {}", checksum_part_two);
     }
 
+    // `day9 --stats` reports on both compactions, and `day9 --visualize [delay_ms] [--files]`
+    // replays one of them in the terminal
+    let args: Vec<String> = env::args().collect();
+    let wants_stats = args.iter().any(|arg| arg == "--stats");
+    let visualize = args.iter().position(|arg| arg == "--visualize");
+    if wants_stats || visualize.is_some() {
+        let initial_array = build_initial_array(&files, &frees);
+        let total_length: usize = files.iter().map(|v| *v as usize).sum();
+
+        let mut block_log = CompactionLog::default();
+        fill_free_spaces_from_back(&initial_array, total_length, Some(&mut block_log));
+        let mut file_log = CompactionLog::default();
+        compact_files(&files, &frees, Some(&mut file_log));
+
+        if wants_stats {
+            let block_layout = apply_moves(&initial_array, &block_log.moves);
+            let block_stats = CompactionStats::new(&block_layout, &block_log);
+            println!("Block compaction: {}", block_stats);
+
+            let file_layout = apply_moves(&initial_array, &file_log.moves);
+            let file_stats = CompactionStats::new(&file_layout, &file_log);
+            println!("Whole-file compaction: {}", file_stats);
+        }
+
+        if let Some(idx) = visualize {
+            let delay_ms = match args.get(idx + 1) {
+                Some(value) if !value.starts_with("--") => value.parse()?,
+                _ => 100,
+            };
+            let moves = if args.iter().any(|arg| arg == "--files") {
+                &file_log.moves
+            } else {
+                &block_log.moves
+            };
+            animate(&initial_array, moves, 100, Duration::from_millis(delay_ms));
+        }
+    }
+
     Ok(())
 }
 
@@ -38,12 +80,12 @@ fn parse_input(line: &str) -> Result<(Vec<u8>, Vec<u8>), Box<dyn Error>> {
 
     let mut i = 0;
     while i < length {
-        let file_count = (bytes[i] - b'0') as u8;
+        let file_count = bytes[i] - b'0';
         i += 1;
 
         let free_count = if i < length {
             // We have a free count available
-            let fc = (bytes[i] - b'0') as u8;
+            let fc = bytes[i] - b'0';
             i += 1;
             fc
         } else {
@@ -62,7 +104,7 @@ fn build_initial_array(files: &[u8], frees: &[u8]) -> Vec<Option<usize>> {
     let total_length: usize = files
         .iter()
         .zip(frees.iter())
-        .map(|(f, fr)| (*f as usize + *fr as usize))
+        .map(|(f, fr)| *f as usize + *fr as usize)
         .sum();
     let mut initial_array = Vec::with_capacity(total_length);
 
@@ -80,9 +122,12 @@ fn build_initial_array(files: &[u8], frees: &[u8]) -> Vec<Option<usize>> {
     initial_array
 }
 
+/// Move single blocks from the back of the disk into the leftmost free blocks.
+/// Each move is added to `log` if given. Blocks always find a free block, so none get stuck.
 fn fill_free_spaces_from_back(
     initial_array: &[Option<usize>],
     total_length: usize,
+    mut log: Option<&mut CompactionLog>,
 ) -> Vec<Option<usize>> {
     fn get_next_non_empty_block_from_back(array: &[Option<usize>], start: usize) -> (usize, usize) {
         let mut idx = start - 1;
@@ -101,92 +146,251 @@ fn fill_free_spaces_from_back(
         if let Some(file_id) = initial_array[i] {
             final_array.push(Some(file_id))
         } else {
-            let (file_id, next_idx) = get_next_non_empty_block_from_back(&initial_array, back_idx);
+            let (file_id, next_idx) = get_next_non_empty_block_from_back(initial_array, back_idx);
             final_array.push(Some(file_id));
             back_idx = next_idx;
+
+            if let Some(log) = log.as_deref_mut() {
+                log.moves.push(Move {
+                    file_id,
+                    from: next_idx,
+                    to: i,
+                    len: 1,
+                });
+            }
         }
     }
 
     final_array
 }
 
-/// Move files to the left starting from the last file.
-/// A file is identified by its file_id and occurs in one contiguous segment.
-/// We try to find a contiguous free space at the left of the array large enough to hold that file.
-/// If found, move the file there and mark old position as free.
-fn move_files_left(files: &[u8], final_array: &mut [Option<usize>]) {
-    let num_files = files.len();
+/// A contiguous run of blocks on the disk.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Span {
+    start: usize,
+    len: usize,
+}
+
+/// The span of every file, indexed by file id, and the free spans between them.
+fn build_spans(files: &[u8], frees: &[u8]) -> (Vec<Span>, Vec<Span>) {
+    let mut file_spans = Vec::with_capacity(files.len());
+    let mut free_spans: Vec<Span> = Vec::with_capacity(frees.len());
+
+    let mut position = 0;
+    for (&fcount, &frcount) in files.iter().zip(frees.iter()) {
+        file_spans.push(Span {
+            start: position,
+            len: fcount as usize,
+        });
+        position += fcount as usize;
+
+        // Free space on both sides of an empty file forms one span
+        match free_spans.last_mut() {
+            Some(last) if last.start + last.len == position => last.len += frcount as usize,
+            _ if frcount > 0 => free_spans.push(Span {
+                start: position,
+                len: frcount as usize,
+            }),
+            _ => {}
+        }
+        position += frcount as usize;
+    }
+
+    (file_spans, free_spans)
+}
+
+/// Move whole files to the leftmost free span that fits, starting from the last file.
+/// Free spans are kept in one min-heap of start positions per length, which is 1 to 9
+/// unless empty files join spans, so the leftmost span that fits is the smallest top
+/// among the heaps for lengths >= the file size.
+/// The space a file leaves behind is never reused, since every file still to be moved
+/// lies to the left of it. Returns the final span of every file, indexed by file id.
+/// Each move, and each file that cannot move, is added to `log` if given.
+fn compact_files(files: &[u8], frees: &[u8], mut log: Option<&mut CompactionLog>) -> Vec<Span> {
+    let (mut file_spans, free_spans) = build_spans(files, frees);
+
+    let longest = free_spans.iter().map(|span| span.len).max().unwrap_or(0);
+    let mut heaps: Vec<BinaryHeap<Reverse<usize>>> = vec![BinaryHeap::new(); longest + 1];
+    for span in free_spans {
+        heaps[span.len].push(Reverse(span.start));
+    }
 
-    // Process files in reverse order: last file first
-    for fid in (0..num_files).rev() {
-        let file_size = files[fid] as usize;
-        if file_size == 0 {
-            continue; // no blocks for this file, skip
+    for (file_id, file) in file_spans.iter_mut().enumerate().rev() {
+        if file.len == 0 {
+            continue;
         }
 
-        // Find the file's segment in final_array
-        if let Some(start) = find_file_segment_start(final_array, fid) {
-            if let Some(free_start) = find_free_segment(final_array, file_size, start) {
-                // Move the file to [free_start .. free_start+file_size]
-                let end = start + file_size;
-                move_file_segment(final_array, start, end, free_start);
+        let leftmost = (file.len..heaps.len())
+            .filter_map(|len| heaps[len].peek().map(|&Reverse(start)| (start, len)))
+            .min();
+        let fits_to_the_left = leftmost.filter(|&(start, _)| start < file.start);
+        let Some((free_start, free_len)) = fits_to_the_left else {
+            // Only a failure if there was free space to the left, just none large enough
+            let free_to_the_left = heaps
+                .iter()
+                .filter_map(|heap| heap.peek())
+                .any(|&Reverse(start)| start < file.start);
+            if let Some(log) = log.as_deref_mut().filter(|_| free_to_the_left) {
+                log.stuck.push(file_id);
             }
+            continue;
+        };
+
+        heaps[free_len].pop();
+        if free_len > file.len {
+            heaps[free_len - file.len].push(Reverse(free_start + file.len));
+        }
+        if let Some(log) = log.as_deref_mut() {
+            log.moves.push(Move {
+                file_id,
+                from: file.start,
+                to: free_start,
+                len: file.len,
+            });
         }
+        file.start = free_start;
     }
+
+    file_spans
 }
 
-/// Find the file segment for a given file_id in array.
-fn find_file_segment_start(final_array: &[Option<usize>], file_id: usize) -> Option<usize> {
-    // find first occurrence of file_id
-    Some(final_array.iter().position(|&b| b == Some(file_id))?)
+/// Compute checksum from file spans, indexed by file id.
+fn compute_span_checksum(file_spans: &[Span]) -> i64 {
+    file_spans
+        .iter()
+        .enumerate()
+        .map(|(file_id, span)| {
+            // Sum of start..start + len
+            let positions = span.len * span.start + span.len * span.len.saturating_sub(1) / 2;
+            file_id as i64 * positions as i64
+        })
+        .sum()
 }
 
-/// Find a contiguous free segment of at least `size` blocks to the left of `limit_index`.
-/// We search from the beginning to limit_index for a run of free blocks large enough.
-fn find_free_segment(
-    final_array: &[Option<usize>],
-    size: usize,
-    limit_index: usize,
-) -> Option<usize> {
-    let mut count = 0;
-    let mut start = 0;
+/// One step of compaction: `len` blocks of a file moved from `from` to `to`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Move {
+    file_id: usize,
+    from: usize,
+    to: usize,
+    len: usize,
+}
 
-    for i in 0..limit_index {
-        if final_array[i].is_none() {
-            count += 1;
-            if count == 1 {
-                start = i;
-            }
-            if count >= size {
-                return Some(start);
-            }
-        } else {
-            count = 0;
-        }
+/// What a compaction did.
+#[derive(Debug, Default)]
+struct CompactionLog {
+    moves: Vec<Move>,
+    /// Files with free space to their left, but no free span large enough to hold them
+    stuck: Vec<usize>,
+}
+
+fn apply_move(layout: &mut [Option<usize>], step: &Move) {
+    for block in &mut layout[step.from..step.from + step.len] {
+        *block = None;
     }
+    for block in &mut layout[step.to..step.to + step.len] {
+        *block = Some(step.file_id);
+    }
+}
+
+/// Replay the moves on the initial layout, keeping the full disk length.
+fn apply_moves(initial_array: &[Option<usize>], moves: &[Move]) -> Vec<Option<usize>> {
+    let mut layout = initial_array.to_vec();
+    for step in moves {
+        apply_move(&mut layout, step);
+    }
+    layout
+}
 
-    None
+/// Draw the disk with the last digit of each file id and '.' for free blocks,
+/// wrapped at `width` blocks per line. With `color`, each file gets an ANSI color.
+fn render_disk(layout: &[Option<usize>], width: usize, color: bool) -> String {
+    layout
+        .chunks(width.max(1))
+        .map(|line| {
+            line.iter()
+                .map(|block| match block {
+                    None => ".".to_string(),
+                    Some(file_id) if color => {
+                        format!("\x1b[{}m{}\x1b[0m", 31 + file_id % 6, file_id % 10)
+                    }
+                    Some(file_id) => (file_id % 10).to_string(),
+                })
+                .collect::<String>()
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
 }
 
-/// Move file segment [file_start..file_end) to [target_start..target_start+(file_end-file_start)).
-/// We'll overwrite the target range with the file's blocks and mark the old range as free.
-fn move_file_segment(
-    final_array: &mut [Option<usize>],
-    file_start: usize,
-    file_end: usize,
-    target_start: usize,
-) {
-    let length = file_end - file_start;
+/// Replay the compaction in the terminal, redrawing the disk after every move.
+fn animate(initial_array: &[Option<usize>], moves: &[Move], width: usize, delay: Duration) {
+    let mut layout = initial_array.to_vec();
+    for (step, mv) in moves.iter().enumerate() {
+        apply_move(&mut layout, mv);
+        // Clear the screen and move the cursor to the top left
+        print!("\x1b[2J\x1b[H");
+        println!("{}", render_disk(&layout, width, true));
+        println!(
+            "Move {} of {}: file {} to {}",
+            step + 1,
+            moves.len(),
+            mv.file_id,
+            mv.to
+        );
+        thread::sleep(delay);
+    }
+}
 
-    let mut file_blocks = Vec::with_capacity(length);
-    file_blocks.extend_from_slice(&final_array[file_start..file_end]);
+/// How well a compaction went.
+#[derive(Debug, PartialEq)]
+struct CompactionStats {
+    /// Share of the free blocks that are still stuck between files
+    fragmentation: f64,
+    /// Longest run of free blocks before the last file block
+    largest_free_span: usize,
+    /// Files that could not move left because no free span was large enough
+    stuck_files: usize,
+    blocks_moved: usize,
+}
 
-    for (i, &b) in file_blocks.iter().enumerate() {
-        final_array[target_start + i] = b;
+impl CompactionStats {
+    fn new(layout: &[Option<usize>], log: &CompactionLog) -> Self {
+        let used_end = layout
+            .iter()
+            .rposition(|block| block.is_some())
+            .map_or(0, |idx| idx + 1);
+        let total_free = layout.iter().filter(|block| block.is_none()).count();
+        let interior_free = layout[..used_end]
+            .iter()
+            .filter(|block| block.is_none())
+            .count();
+
+        let largest_free_span = layout[..used_end]
+            .split(|block| block.is_some())
+            .map(|run| run.len())
+            .max()
+            .unwrap_or(0);
+
+        CompactionStats {
+            fragmentation: if total_free == 0 {
+                0.0
+            } else {
+                interior_free as f64 / total_free as f64
+            },
+            largest_free_span,
+            stuck_files: log.stuck.len(),
+            blocks_moved: log.moves.iter().map(|step| step.len).sum(),
+        }
     }
+}
 
-    for i in file_start..file_end {
-        final_array[i] = None;
+impl std::fmt::Display for CompactionStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "fragmentation {:.3}, largest free span {}, stuck files {}, blocks moved {}",
+            self.fragmentation, self.largest_free_span, self.stuck_files, self.blocks_moved
+        )
     }
 }
 
@@ -204,6 +408,7 @@ fn compute_checksum(array: &[Option<usize>]) -> i64 {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use aoc2024::rng::Lcg;
 
     const TEST_DATA: &str = "2333133121414131402";
 
@@ -212,7 +417,7 @@ mod tests {
         let (files, frees) = parse_input(TEST_DATA).unwrap();
         let initial_array = build_initial_array(&files, &frees);
         let total_length: usize = files.iter().map(|v| *v as usize).sum();
-        let final_array = fill_free_spaces_from_back(&initial_array, total_length);
+        let final_array = fill_free_spaces_from_back(&initial_array, total_length, None);
         let checksum = compute_checksum(&final_array);
 
         let expected_checksum = 1928;
@@ -222,11 +427,186 @@ mod tests {
     #[test]
     fn test_part_two() {
         let (files, frees) = parse_input(TEST_DATA).unwrap();
-        let mut final_array = build_initial_array(&files, &frees);
-        move_files_left(&files, &mut final_array);
-        let checksum = compute_checksum(&final_array);
+        let file_spans = compact_files(&files, &frees, None);
+        let checksum = compute_span_checksum(&file_spans);
 
         let expected_checksum = 2858;
         assert_eq!(checksum, expected_checksum);
     }
+
+    /// Move files to the left starting from the last file.
+    /// A file is identified by its file_id and occurs in one contiguous segment.
+    /// We try to find a contiguous free space at the left of the array large enough to hold that file.
+    /// If found, move the file there and mark old position as free.
+    fn move_files_left(files: &[u8], final_array: &mut [Option<usize>]) {
+        let num_files = files.len();
+
+        // Process files in reverse order: last file first
+        for fid in (0..num_files).rev() {
+            let file_size = files[fid] as usize;
+            if file_size == 0 {
+                continue; // no blocks for this file, skip
+            }
+
+            // Find the file's segment in final_array
+            if let Some(start) = find_file_segment_start(final_array, fid) {
+                if let Some(free_start) = find_free_segment(final_array, file_size, start) {
+                    // Move the file to [free_start .. free_start+file_size]
+                    let end = start + file_size;
+                    move_file_segment(final_array, start, end, free_start);
+                }
+            }
+        }
+    }
+
+    /// Find the file segment for a given file_id in array.
+    fn find_file_segment_start(final_array: &[Option<usize>], file_id: usize) -> Option<usize> {
+        // find first occurrence of file_id
+        final_array.iter().position(|&b| b == Some(file_id))
+    }
+
+    /// Find a contiguous free segment of at least `size` blocks to the left of `limit_index`.
+    /// We search from the beginning to limit_index for a run of free blocks large enough.
+    fn find_free_segment(
+        final_array: &[Option<usize>],
+        size: usize,
+        limit_index: usize,
+    ) -> Option<usize> {
+        let mut count = 0;
+        let mut start = 0;
+
+        for (i, block) in final_array.iter().enumerate().take(limit_index) {
+            if block.is_none() {
+                count += 1;
+                if count == 1 {
+                    start = i;
+                }
+                if count >= size {
+                    return Some(start);
+                }
+            } else {
+                count = 0;
+            }
+        }
+
+        None
+    }
+
+    /// Move file segment [file_start..file_end) to [target_start..target_start+(file_end-file_start)).
+    /// We'll overwrite the target range with the file's blocks and mark the old range as free.
+    fn move_file_segment(
+        final_array: &mut [Option<usize>],
+        file_start: usize,
+        file_end: usize,
+        target_start: usize,
+    ) {
+        let length = file_end - file_start;
+
+        let mut file_blocks = Vec::with_capacity(length);
+        file_blocks.extend_from_slice(&final_array[file_start..file_end]);
+
+        for (i, &b) in file_blocks.iter().enumerate() {
+            final_array[target_start + i] = b;
+        }
+
+        for block in &mut final_array[file_start..file_end] {
+            *block = None;
+        }
+    }
+
+    #[test]
+    fn test_matches_block_array() {
+        let mut inputs = vec![TEST_DATA.to_string()];
+
+        // Pseudo-random disk maps, including zero-length files and free spans
+        let mut rng = Lcg::new(9);
+        for _ in 0..200 {
+            let length = 1 + rng.below(40);
+            let map: String = (0..length)
+                .map(|_| char::from(b'0' + rng.below(10) as u8))
+                .collect();
+            inputs.push(map);
+        }
+
+        for input in inputs {
+            let (files, frees) = parse_input(&input).unwrap();
+            let mut final_array = build_initial_array(&files, &frees);
+            move_files_left(&files, &mut final_array);
+
+            let file_spans = compact_files(&files, &frees, None);
+            assert_eq!(
+                compute_span_checksum(&file_spans),
+                compute_checksum(&final_array),
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_recorded_moves() {
+        let (files, frees) = parse_input(TEST_DATA).unwrap();
+        let initial_array = build_initial_array(&files, &frees);
+        let total_length: usize = files.iter().map(|v| *v as usize).sum();
+
+        let mut block_log = CompactionLog::default();
+        fill_free_spaces_from_back(&initial_array, total_length, Some(&mut block_log));
+        let block_layout = apply_moves(&initial_array, &block_log.moves);
+        assert_eq!(
+            render_disk(&block_layout, 100, false),
+            "0099811188827773336446555566.............."
+        );
+
+        let mut file_log = CompactionLog::default();
+        compact_files(&files, &frees, Some(&mut file_log));
+        let file_layout = apply_moves(&initial_array, &file_log.moves);
+        assert_eq!(
+            render_disk(&file_layout, 100, false),
+            "00992111777.44.333....5555.6666.....8888.."
+        );
+        assert_eq!(
+            file_log.moves[0],
+            Move {
+                file_id: 9,
+                from: 40,
+                to: 2,
+                len: 2,
+            }
+        );
+    }
+
+    #[test]
+    fn test_compaction_stats() {
+        let (files, frees) = parse_input(TEST_DATA).unwrap();
+        let initial_array = build_initial_array(&files, &frees);
+        let total_length: usize = files.iter().map(|v| *v as usize).sum();
+
+        let mut block_log = CompactionLog::default();
+        fill_free_spaces_from_back(&initial_array, total_length, Some(&mut block_log));
+        let block_layout = apply_moves(&initial_array, &block_log.moves);
+        let expected_block_stats = CompactionStats {
+            fragmentation: 0.0,
+            largest_free_span: 0,
+            stuck_files: 0,
+            blocks_moved: 12,
+        };
+        assert_eq!(
+            CompactionStats::new(&block_layout, &block_log),
+            expected_block_stats
+        );
+
+        let mut file_log = CompactionLog::default();
+        compact_files(&files, &frees, Some(&mut file_log));
+        let file_layout = apply_moves(&initial_array, &file_log.moves);
+        let expected_file_stats = CompactionStats {
+            fragmentation: 12.0 / 14.0,
+            largest_free_span: 5,
+            stuck_files: 4,
+            blocks_moved: 8,
+        };
+        assert_eq!(
+            CompactionStats::new(&file_layout, &file_log),
+            expected_file_stats
+        );
+    }
 }
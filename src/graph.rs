@@ -0,0 +1,160 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::hash::Hash;
+
+/// An undirected graph stored as adjacency sets.
+pub struct Graph<T> {
+    adjacency: HashMap<T, HashSet<T>>,
+}
+
+impl<T> Default for Graph<T> {
+    fn default() -> Self {
+        Graph {
+            adjacency: HashMap::new(),
+        }
+    }
+}
+
+impl<T: Clone + Eq + Hash + Ord> Graph<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add an undirected edge between `a` and `b`, creating the nodes if needed.
+    pub fn add_edge(&mut self, a: T, b: T) {
+        self.adjacency
+            .entry(a.clone())
+            .or_default()
+            .insert(b.clone());
+        self.adjacency.entry(b).or_default().insert(a);
+    }
+
+    pub fn nodes(&self) -> impl Iterator<Item = &T> {
+        self.adjacency.keys()
+    }
+
+    pub fn neighbors(&self, node: &T) -> Option<&HashSet<T>> {
+        self.adjacency.get(node)
+    }
+
+    pub fn has_edge(&self, a: &T, b: &T) -> bool {
+        self.adjacency
+            .get(a)
+            .is_some_and(|neighbors| neighbors.contains(b))
+    }
+
+    /// All triangles in the graph, each listed once with its nodes in ascending order.
+    pub fn triangles(&self) -> Vec<[T; 3]> {
+        let mut triangles = Vec::new();
+        for (a, neighbors_a) in &self.adjacency {
+            for b in neighbors_a.iter().filter(|&b| b > a) {
+                for c in neighbors_a.iter().filter(|&c| c > b) {
+                    if self.has_edge(b, c) {
+                        triangles.push([a.clone(), b.clone(), c.clone()]);
+                    }
+                }
+            }
+        }
+        triangles
+    }
+
+    /// A maximum clique, sorted ascending, found with Bron–Kerbosch with pivoting.
+    pub fn max_clique(&self) -> Vec<T> {
+        let mut best = Vec::new();
+        let candidates: BTreeSet<T> = self.adjacency.keys().cloned().collect();
+        self.bron_kerbosch(&mut Vec::new(), candidates, BTreeSet::new(), &mut best);
+        best.sort();
+        best
+    }
+
+    /// Extend the clique `r` with nodes from `p`, skipping nodes in `x` that were
+    /// already explored. The pivot is the node of `p ∪ x` with the most neighbors in `p`,
+    /// so only its non-neighbors need to be branched on.
+    fn bron_kerbosch(
+        &self,
+        r: &mut Vec<T>,
+        mut p: BTreeSet<T>,
+        mut x: BTreeSet<T>,
+        best: &mut Vec<T>,
+    ) {
+        if p.is_empty() {
+            if x.is_empty() && r.len() > best.len() {
+                *best = r.clone();
+            }
+            return;
+        }
+
+        // No extension of r can beat the best clique found so far
+        if r.len() + p.len() <= best.len() {
+            return;
+        }
+
+        let empty = HashSet::new();
+        let pivot = p
+            .iter()
+            .chain(x.iter())
+            .max_by_key(|&u| {
+                let neighbors = self.neighbors(u).unwrap_or(&empty);
+                p.iter().filter(|&v| neighbors.contains(v)).count()
+            })
+            .cloned();
+        let pivot_neighbors = pivot
+            .as_ref()
+            .and_then(|u| self.neighbors(u))
+            .unwrap_or(&empty);
+
+        let branch_nodes: Vec<T> = p
+            .iter()
+            .filter(|&v| !pivot_neighbors.contains(v))
+            .cloned()
+            .collect();
+
+        for v in branch_nodes {
+            let neighbors = self.neighbors(&v).unwrap_or(&empty);
+            let next_p = p
+                .iter()
+                .filter(|&u| neighbors.contains(u))
+                .cloned()
+                .collect();
+            let next_x = x
+                .iter()
+                .filter(|&u| neighbors.contains(u))
+                .cloned()
+                .collect();
+
+            r.push(v.clone());
+            self.bron_kerbosch(r, next_p, next_x, best);
+            r.pop();
+
+            p.remove(&v);
+            x.insert(v);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_triangles_and_max_clique() {
+        let mut graph = Graph::new();
+        // A 4-clique 1-2-3-4 with a tail 4-5 and a separate triangle 5-6-7
+        for (a, b) in [
+            (1, 2),
+            (1, 3),
+            (1, 4),
+            (2, 3),
+            (2, 4),
+            (3, 4),
+            (4, 5),
+            (5, 6),
+            (5, 7),
+            (6, 7),
+        ] {
+            graph.add_edge(a, b);
+        }
+
+        assert_eq!(graph.triangles().len(), 5);
+        assert_eq!(graph.max_clique(), vec![1, 2, 3, 4]);
+    }
+}
@@ -1,3 +1,6 @@
+pub mod graph;
+pub mod rng;
+
 use reqwest::blocking::Client;
 use reqwest::header::COOKIE;
 use std::env;
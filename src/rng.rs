@@ -0,0 +1,21 @@
+/// A small deterministic pseudo-random generator (a 64-bit linear congruential
+/// generator), used to build reproducible random inputs for cross-checks in tests.
+pub struct Lcg {
+    state: u64,
+}
+
+impl Lcg {
+    pub fn new(seed: u64) -> Self {
+        Lcg { state: seed }
+    }
+
+    /// The next value in `0..bound`.
+    pub fn below(&mut self, bound: u64) -> u64 {
+        self.state = self
+            .state
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        // The high bits have the longest period
+        (self.state >> 33) % bound
+    }
+}